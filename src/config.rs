@@ -0,0 +1,397 @@
+use std::{
+    collections::HashSet,
+    fs,
+    net::{SocketAddr, ToSocketAddrs},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use tracing::Level;
+use twilight_model::id::{marker::GuildMarker, Id};
+
+// Used when the CONFIG_PATH environment variable is not set
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Bot configuration, read from a TOML file and then overridden by environment variables.
+/// See `config.example.toml` for every key
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub discord_token: String,
+    pub log_level: String,
+    pub lavalink: Vec<LavalinkNode>,
+    pub shards: ShardsConfig,
+    pub commands: CommandsConfig,
+    pub storage: StorageConfig,
+    pub library: LibraryConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LavalinkNode {
+    /// `host:port` of the node, the host can be a domain name
+    pub address: String,
+    pub password: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShardsConfig {
+    /// Total number of shards of the bot, discord's recommendation is used when unset
+    pub total: Option<u64>,
+    /// First shard run by this process, needs `total`
+    pub start: Option<u64>,
+    /// Shard after the last one run by this process, needs `total`
+    pub end: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandsConfig {
+    pub registration: Registration,
+    /// Guilds the commands are registered in with the `guilds` and `both` registration modes
+    pub guilds: Vec<Id<GuildMarker>>,
+    /// Remove the commands registered where the registration mode doesn't register them,
+    /// e.g. the guild commands left after switching to `global`
+    pub prune: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Registration {
+    /// Available everywhere, changes can take a while to show up
+    #[default]
+    Global,
+    /// Only in the configured guilds, changes show up right away
+    Guilds,
+    /// Globally and in the configured guilds
+    Both,
+    /// Register nothing, with `prune` every registered command is removed
+    Off,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Directory where the bot keeps what it needs across restarts
+    pub data_dir: PathBuf,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LibraryConfig {
+    /// Directory of audio files played through lavalink's local source, so lavalink has to
+    /// run on the same machine. The library commands are disabled when unset
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            discord_token: String::new(),
+            log_level: "info".to_owned(),
+            lavalink: Vec::new(),
+            shards: ShardsConfig::default(),
+            commands: CommandsConfig::default(),
+            storage: StorageConfig::default(),
+            library: LibraryConfig::default(),
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("data"),
+        }
+    }
+}
+
+impl Config {
+    /// Read the file at CONFIG_PATH (if there is one), apply the environment overrides and validate
+    pub fn load() -> anyhow::Result<Self> {
+        let path = std::env::var("CONFIG_PATH").unwrap_or(DEFAULT_CONFIG_PATH.to_owned());
+
+        let mut config = Self::from_file(Path::new(&path))?;
+        let mut errors = config.apply_env();
+        errors.extend(config.validate());
+
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "Invalid configuration ({}):\n  - {}",
+                path,
+                errors.join("\n  - ")
+            );
+        }
+
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> anyhow::Result<Self> {
+        // Running only with environment variables is fine
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("Failed to read {}: {}", path.display(), err))?;
+
+        toml::from_str(&content)
+            .map_err(|err| anyhow::anyhow!("Failed to parse {}: {}", path.display(), err))
+    }
+
+    /// Environment variables take priority over the file,
+    /// the names match the ones of the old .env setup
+    fn apply_env(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if let Ok(token) = std::env::var("DISCORD_TOKEN") {
+            self.discord_token = token;
+        }
+        if let Ok(level) = std::env::var("LOG_LEVEL") {
+            self.log_level = level;
+        }
+        if let Ok(data_dir) = std::env::var("DATA_DIR") {
+            self.storage.data_dir = PathBuf::from(data_dir);
+        }
+        if let Ok(library) = std::env::var("LIBRARY_DIR") {
+            self.library.path = Some(PathBuf::from(library));
+        }
+
+        // A node given through the environment replaces the ones of the file
+        match (
+            std::env::var("LAVALINK_HOST"),
+            std::env::var("LAVALINK_SECRET"),
+        ) {
+            (Ok(address), Ok(password)) => self.lavalink = vec![LavalinkNode { address, password }],
+            (Ok(_), Err(_)) | (Err(_), Ok(_)) => {
+                errors.push("LAVALINK_HOST and LAVALINK_SECRET have to be set together".to_owned())
+            }
+            _ => {}
+        }
+
+        if let Ok(total) = std::env::var("SHARD_TOTAL") {
+            match total.parse() {
+                Ok(total) => self.shards.total = Some(total),
+                Err(_) => errors.push(format!("SHARD_TOTAL is not a number: {}", total)),
+            }
+        }
+
+        if let Ok(registration) = std::env::var("COMMAND_REGISTRATION") {
+            match registration.as_str() {
+                "global" => self.commands.registration = Registration::Global,
+                "guilds" => self.commands.registration = Registration::Guilds,
+                "both" => self.commands.registration = Registration::Both,
+                "off" => self.commands.registration = Registration::Off,
+                _ => errors.push(format!(
+                    "COMMAND_REGISTRATION has to be global, guilds, both or off, not {}",
+                    registration
+                )),
+            }
+        }
+
+        if let Ok(prune) = std::env::var("COMMAND_PRUNE") {
+            match prune.parse() {
+                Ok(prune) => self.commands.prune = prune,
+                Err(_) => errors.push(format!(
+                    "COMMAND_PRUNE has to be true or false, not {}",
+                    prune
+                )),
+            }
+        }
+
+        // Kept from the .env setup, used with COMMAND_REGISTRATION=guilds
+        if let Ok(guild) = std::env::var("TEST_GUILD") {
+            match guild.parse::<u64>().ok().and_then(Id::new_checked) {
+                Some(guild_id) => self.commands.guilds = vec![guild_id],
+                None => errors.push(format!("TEST_GUILD is not a guild id: {}", guild)),
+            }
+        }
+
+        errors
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.discord_token.trim().is_empty() {
+            errors.push("discord_token is missing (or set DISCORD_TOKEN)".to_owned());
+        }
+
+        if Level::from_str(&self.log_level).is_err() {
+            errors.push(format!(
+                "log_level has to be trace, debug, info, warn or error, not {}",
+                self.log_level
+            ));
+        }
+
+        if self.lavalink.is_empty() {
+            errors.push(
+                "At least one [[lavalink]] node is needed (or set LAVALINK_HOST and LAVALINK_SECRET)"
+                    .to_owned(),
+            );
+        }
+        let mut addresses = HashSet::new();
+        for node in &self.lavalink {
+            if let Err(err) = node.socket_addr() {
+                errors.push(err.to_string());
+            }
+            if node.password.is_empty() {
+                errors.push(format!("Lavalink node {} has no password", node.address));
+            }
+            if !addresses.insert(&node.address) {
+                errors.push(format!("Lavalink node {} is listed twice", node.address));
+            }
+        }
+
+        match (self.shards.total, self.shards.start, self.shards.end) {
+            (Some(0), _, _) => errors.push("shards.total has to be at least 1".to_owned()),
+            (None, Some(_), _) | (None, _, Some(_)) => {
+                errors.push("shards.start and shards.end need shards.total".to_owned())
+            }
+            (Some(total), start, end) => {
+                let (start, end) = (start.unwrap_or(0), end.unwrap_or(total));
+                if start >= end || end > total {
+                    errors.push(format!(
+                        "Shards {}..{} are not a valid range of the {} shards",
+                        start, end, total
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(path) = &self.library.path {
+            if !path.is_dir() {
+                errors.push(format!(
+                    "library.path {} is not a directory",
+                    path.display()
+                ));
+            }
+        }
+
+        let needs_guilds = matches!(
+            self.commands.registration,
+            Registration::Guilds | Registration::Both
+        );
+        if needs_guilds && self.commands.guilds.is_empty() {
+            errors.push(
+                "commands.guilds can't be empty with the guilds and both registration modes"
+                    .to_owned(),
+            );
+        }
+
+        errors
+    }
+
+    pub fn log_level(&self) -> Level {
+        Level::from_str(&self.log_level).unwrap_or(Level::INFO)
+    }
+}
+
+impl LavalinkNode {
+    pub fn socket_addr(&self) -> anyhow::Result<SocketAddr> {
+        self.address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or(anyhow::anyhow!(
+                "Lavalink node address {} can't be resolved, it has to be host:port",
+                self.address
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Config {
+        toml::from_str(content).unwrap()
+    }
+
+    const VALID: &str = r#"
+        discord_token = "token"
+
+        [[lavalink]]
+        address = "127.0.0.1:2333"
+        password = "youshallnotpass"
+    "#;
+
+    #[test]
+    fn accepts_a_minimal_config() {
+        assert!(parse(VALID).validate().is_empty());
+    }
+
+    #[test]
+    fn refuses_unknown_keys() {
+        assert!(toml::from_str::<Config>("discord_tokn = \"token\"").is_err());
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let config = parse(
+            r#"
+            log_level = "loud"
+
+            [shards]
+            start = 1
+            "#,
+        );
+
+        // The token, the log level, the nodes and the shards
+        assert_eq!(config.validate().len(), 4);
+    }
+
+    #[test]
+    fn checks_the_lavalink_nodes() {
+        let mut config = parse(VALID);
+        config.lavalink.push(LavalinkNode {
+            address: "127.0.0.1:2333".to_owned(),
+            password: String::new(),
+        });
+        config.lavalink.push(LavalinkNode {
+            address: "no port".to_owned(),
+            password: "password".to_owned(),
+        });
+
+        // A missing password, a duplicate and an unresolvable address
+        assert_eq!(config.validate().len(), 3);
+    }
+
+    #[test]
+    fn checks_the_shard_range() {
+        let mut config = parse(VALID);
+
+        config.shards = ShardsConfig {
+            total: Some(4),
+            start: Some(2),
+            end: None,
+        };
+        assert!(config.validate().is_empty());
+
+        config.shards.end = Some(2);
+        assert_eq!(config.validate().len(), 1);
+
+        config.shards = ShardsConfig {
+            total: Some(4),
+            start: None,
+            end: Some(5),
+        };
+        assert_eq!(config.validate().len(), 1);
+
+        config.shards.total = Some(0);
+        assert_eq!(config.validate().len(), 1);
+    }
+
+    #[test]
+    fn guild_registration_needs_guilds() {
+        let mut config = parse(VALID);
+        config.commands.registration = Registration::Both;
+        assert_eq!(config.validate().len(), 1);
+
+        config.commands.guilds = vec![Id::new(1)];
+        assert!(config.validate().is_empty());
+    }
+}
@@ -1,94 +1,213 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
 
 use dashmap::DashMap;
 use hyper::{client::HttpConnector, Client as HyperClient};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use serde_json::Value;
 use twilight_cache_inmemory::{InMemoryCache, ResourceType};
 use twilight_gateway::{MessageSender, ShardId};
 use twilight_http::{client::InteractionClient, Client as HttpClient};
-use twilight_lavalink::Lavalink;
+use twilight_lavalink::{http::Track as TwilightTrack, model::Play, Lavalink};
 use twilight_model::{
-    application::interaction::Interaction,
-    channel::message::Embed,
-    http::interaction::{InteractionResponse, InteractionResponseType},
+    application::{command::Command, interaction::Interaction},
+    channel::{
+        message::{Embed, MessageFlags},
+        ChannelType,
+    },
+    http::{
+        attachment::Attachment,
+        interaction::{InteractionResponse, InteractionResponseType},
+    },
     id::{
-        marker::{ApplicationMarker, GuildMarker},
+        marker::{ApplicationMarker, ChannelMarker, GuildMarker, MessageMarker, UserMarker},
         Id,
     },
 };
-use twilight_util::builder::InteractionResponseDataBuilder;
+use twilight_standby::Standby;
+use twilight_util::builder::{
+    embed::{EmbedBuilder, EmbedFieldBuilder},
+    InteractionResponseDataBuilder,
+};
 
-use crate::{interactions, queue::TracksQueue};
+use crate::{
+    config::{Config, Registration},
+    error::UserError,
+    favorites::FavoritesStore,
+    history::History,
+    inactivity::Inactivity,
+    interactions,
+    library::Library,
+    queue::TracksQueue,
+    settings::SettingsStore,
+    tables::TableStore,
+    tags::TagStore,
+    track::Track,
+    utils::truncate_lines,
+};
 
 pub struct Context {
+    pub config: Config,
     pub app_id: Id<ApplicationMarker>,
+    pub user_id: Id<UserMarker>,
     pub http_client: HttpClient,
-    pub hyper_client: HyperClient<HttpConnector>,
+    /// Lavalink nodes over http, attachments from discord's CDN over https
+    pub hyper_client: HyperClient<HttpsConnector<HttpConnector>>,
     pub cache: InMemoryCache,
     pub lavalink: Lavalink,
+    pub standby: Standby,
     pub shard_senders: DashMap<ShardId, MessageSender>,
     pub queues: DashMap<Id<GuildMarker>, Arc<Mutex<TracksQueue>>>,
+    pub settings: SettingsStore,
+    pub favorites: FavoritesStore,
+    pub history: History,
+    pub library: Library,
+    pub tags: TagStore,
+    pub tables: TableStore,
+    // Users that voted to skip the current track of each guild
+    pub skip_votes: DashMap<Id<GuildMarker>, HashSet<Id<UserMarker>>>,
+    // Now playing message of each guild, /voteskip shows its votes on the button too
+    pub now_playing_messages: DashMap<Id<GuildMarker>, (Id<ChannelMarker>, Id<MessageMarker>)>,
+    pub inactivity: Inactivity,
 }
 
 impl Context {
-    pub async fn new(token: String) -> anyhow::Result<self::Context> {
+    pub async fn new(config: Config) -> anyhow::Result<self::Context> {
         // Create http client
-        let http_client = HttpClient::new(token);
+        let http_client = HttpClient::new(config.discord_token.clone());
 
         let cache = InMemoryCache::builder()
-            .resource_types(ResourceType::MESSAGE | ResourceType::VOICE_STATE)
+            .resource_types(
+                ResourceType::MESSAGE
+                    | ResourceType::CHANNEL
+                    | ResourceType::VOICE_STATE
+                    | ResourceType::USER
+                    | ResourceType::MEMBER
+                    | ResourceType::ROLE,
+            )
             .build();
 
         let user_id = http_client.current_user().await?.model().await?.id;
 
-        let lavalink = Lavalink::new(user_id, 1u64);
+        let lavalink = Lavalink::new(user_id, config.shards.total.unwrap_or(1));
 
-        let app_id = http_client.current_user_application().await?.model().await?.id;
+        let app_id = http_client
+            .current_user_application()
+            .await?
+            .model()
+            .await?
+            .id;
+
+        let settings = SettingsStore::load(config.storage.data_dir.join("settings.json"))?;
+        let favorites = FavoritesStore::load(config.storage.data_dir.join("favorites.json"))?;
+        let history = History::open(&config.storage.data_dir.join("history.sqlite"))?;
+        let library = Library::new(config.library.path.clone());
+        let tags = TagStore::load(config.storage.data_dir.join("tags.json"))?;
+        let tables = TableStore::load(config.storage.data_dir.join("tables.json"))?;
 
         Ok(Self {
+            config,
             app_id,
+            user_id,
             http_client,
-            hyper_client: HyperClient::new(),
+            hyper_client: HyperClient::builder().build(
+                HttpsConnectorBuilder::new()
+                    .with_native_roots()
+                    .https_or_http()
+                    .enable_http1()
+                    .build(),
+            ),
             cache,
             lavalink,
+            standby: Standby::new(),
             shard_senders: DashMap::default(),
             queues: DashMap::default(),
+            settings,
+            favorites,
+            history,
+            library,
+            tags,
+            tables,
+            skip_votes: DashMap::default(),
+            now_playing_messages: DashMap::default(),
+            inactivity: Inactivity::default(),
         })
     }
 
-    pub async fn interaction_client(&self) -> anyhow::Result<InteractionClient> {
+    pub async fn interaction_client(&self) -> anyhow::Result<InteractionClient<'_>> {
         Ok(self.http_client.interaction(self.app_id))
     }
 
-    /// Setup all the slash commands (currently only per guild)
-    /// TODO: Add support for global commands
+    /// Register the application commands as the configured registration mode says
     pub async fn setup_commands(&self) -> anyhow::Result<()> {
-        let guild_commands = vec![
-            interactions::join::command(),
-            interactions::leave::command(),
-            interactions::play::command(),
-            interactions::pause::command(),
-            interactions::resume::command(),
-            interactions::stop::command(),
-            interactions::skip::command(),
-            interactions::shuffle::command(),
-            interactions::queue::command(),
-            interactions::now_playing::command(),
-            interactions::lup::command(),
-        ];
-
-        let global_commands = guild_commands.clone();
-        // Application command registering (doing it per guild as doing it globally can take a couple of minutes)
-        self.interaction_client()
-            .await?
-            .set_guild_commands(
-                Id::new(std::env::var("TEST_GUILD")?.parse::<u64>()?),
-                &guild_commands,
-            )
-            .await?;
-        self.interaction_client()
-            .await?
-            .set_global_commands(&global_commands)
-            .await?;
+        let commands = interactions::commands();
+        let commands_config = &self.config.commands;
+
+        // Registering per guild is instant, globally it can take a couple of minutes
+        let (global, guilds) = match commands_config.registration {
+            Registration::Global => (true, false),
+            Registration::Guilds => (false, true),
+            Registration::Both => (true, true),
+            Registration::Off => (false, false),
+        };
+
+        if global {
+            self.sync_commands(None, &commands).await?;
+        } else if commands_config.prune {
+            self.sync_commands(None, &[]).await?;
+        }
+
+        for guild_id in &commands_config.guilds {
+            if guilds {
+                self.sync_commands(Some(*guild_id), &commands).await?;
+            } else if commands_config.prune {
+                self.sync_commands(Some(*guild_id), &[]).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Overwrite the commands registered globally (or in the guild) only when they differ
+    /// from `commands`. Overwriting also removes the registered commands that are not in `commands`
+    async fn sync_commands(
+        &self,
+        guild_id: Option<Id<GuildMarker>>,
+        commands: &[Command],
+    ) -> anyhow::Result<()> {
+        let client = self.interaction_client().await?;
+        let scope = guild_id.map_or("global".to_owned(), |guild_id| {
+            format!("guild {}", guild_id)
+        });
+
+        let registered = match guild_id {
+            Some(guild_id) => client.guild_commands(guild_id).await?.models().await?,
+            None => client.global_commands().await?.models().await?,
+        };
+
+        if same_commands(&registered, commands, guild_id.is_some())? {
+            tracing::info!("Commands ({}) are up to date", scope);
+            return Ok(());
+        }
+
+        match guild_id {
+            Some(guild_id) => {
+                client.set_guild_commands(guild_id, commands).await?;
+            }
+            None => {
+                client.set_global_commands(commands).await?;
+            }
+        }
+
+        tracing::info!(
+            "Registered {} commands ({}), {} were registered before",
+            commands.len(),
+            scope,
+            registered.len()
+        );
+
         Ok(())
     }
 
@@ -96,17 +215,145 @@ impl Context {
         self.shard_senders.insert(shard_id, sender);
     }
 
+    /// Message sender of the shard that receives the guild's events
+    pub fn shard_sender(&self, guild_id: Id<GuildMarker>) -> Option<MessageSender> {
+        self.shard_senders
+            .iter()
+            .find(|entry| entry.key().number() == (guild_id.get() >> 22) % entry.key().total())
+            .map(|entry| entry.value().clone())
+    }
+
+    /// Voice channel the bot is connected to in the guild
+    pub fn bot_voice_channel(&self, guild_id: Id<GuildMarker>) -> Option<Id<ChannelMarker>> {
+        self.cache
+            .voice_state(self.user_id, guild_id)
+            .map(|voice_state| voice_state.channel_id())
+    }
+
+    pub fn is_stage_channel(&self, channel_id: Id<ChannelMarker>) -> bool {
+        self.cache
+            .channel(channel_id)
+            .is_some_and(|channel| channel.kind == ChannelType::GuildStageVoice)
+    }
+
+    /// Name of the user for places where mentions don't render, like exported files
+    pub fn user_name(&self, user_id: Id<UserMarker>) -> String {
+        self.cache
+            .user(user_id)
+            .map(|user| user.name.clone())
+            .unwrap_or(user_id.to_string())
+    }
+
+    /// Users in the voice channel, without the bot and any other bot account
+    pub fn humans_in_channel(&self, channel_id: Id<ChannelMarker>) -> Vec<Id<UserMarker>> {
+        let Some(voice_states) = self.cache.voice_channel_states(channel_id) else {
+            return Vec::new();
+        };
+
+        voice_states
+            .map(|voice_state| voice_state.user_id())
+            .filter(|user_id| {
+                *user_id != self.user_id && !self.cache.user(*user_id).is_some_and(|user| user.bot)
+            })
+            .collect()
+    }
+
     pub fn get_queue(&self, guild_id: Id<GuildMarker>) -> Option<Arc<Mutex<TracksQueue>>> {
         self.queues.get(&guild_id).map(|mapref| Arc::clone(&mapref))
     }
 
     pub fn get_or_create_queue(&self, guild_id: Id<GuildMarker>) -> Arc<Mutex<TracksQueue>> {
-        self.get_queue(guild_id).unwrap_or(
+        self.get_queue(guild_id).unwrap_or_else(|| {
             self.queues
                 .entry(guild_id)
-                .or_insert(Arc::new(Mutex::new(TracksQueue::new())))
-                .clone(),
-        )
+                .or_insert_with(|| {
+                    let mut queue = TracksQueue::new();
+                    queue.loop_mode = self.settings.get(guild_id).default_loop;
+                    Arc::new(Mutex::new(queue))
+                })
+                .clone()
+        })
+    }
+
+    /// Queue the tracks that fit the guild's limits and start the queue if nothing is playing.
+    /// Returns the limit check of each track, in the order they were given
+    pub async fn enqueue(
+        &self,
+        guild_id: Id<GuildMarker>,
+        tracks: Vec<TwilightTrack>,
+        channel_id: Id<ChannelMarker>,
+        requester: Id<UserMarker>,
+    ) -> anyhow::Result<Vec<Result<(), UserError>>> {
+        let player = self.lavalink.player(guild_id).await?;
+        let settings = self.settings.get(guild_id);
+        let queue_arc = self.get_or_create_queue(guild_id);
+        let queue = queue_arc.lock().unwrap();
+
+        let checks = tracks
+            .into_iter()
+            .map(|track| {
+                settings.check_track(queue.len(), &track)?;
+                queue.push(Track::new(track, channel_id, requester));
+                Ok(())
+            })
+            .collect();
+
+        // Play is sent with no_replace, so it only starts the queue if nothing is playing
+        if let Ok(first) = queue.peek() {
+            player.send(Play::from((guild_id, first.track(), first.start_time)))?;
+        }
+
+        Ok(checks)
+    }
+
+    /// Queue the tracks of a command that adds many at once and answer its deferred response
+    /// with what was queued and what failed. Each track comes with the line listing it,
+    /// `failed` already holds the entries that couldn't be loaded
+    pub async fn enqueue_with_summary(
+        &self,
+        interaction: &Interaction,
+        guild_id: Id<GuildMarker>,
+        tracks: Vec<(String, TwilightTrack)>,
+        mut failed: Vec<String>,
+    ) -> anyhow::Result<()> {
+        let channel_id = interaction
+            .channel
+            .as_ref()
+            .map(|channel| channel.id)
+            .ok_or(anyhow::anyhow!("Invalid channel id"))?;
+        let requester = interaction
+            .author_id()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        let (labels, tracks): (Vec<_>, Vec<_>) = tracks.into_iter().unzip();
+        let checks = self
+            .enqueue(guild_id, tracks, channel_id, requester)
+            .await?;
+
+        let mut queued = Vec::new();
+        for (label, check) in labels.into_iter().zip(checks) {
+            match check {
+                Ok(()) => queued.push(label),
+                Err(err) => failed.push(format!("{} ({})", label, err)),
+            }
+        }
+
+        let mut embed_builder = EmbedBuilder::new()
+            .color(self.settings.get(guild_id).embed_color)
+            .title(format!("Queued {} tracks", queued.len()));
+
+        if !queued.is_empty() {
+            embed_builder = embed_builder
+                .field(EmbedFieldBuilder::new("Queued", truncate_lines(&queued, 1024)).build());
+        }
+
+        if !failed.is_empty() {
+            embed_builder = embed_builder
+                .field(EmbedFieldBuilder::new("Failed", truncate_lines(&failed, 1024)).build());
+        }
+
+        self.update_embed_response(interaction, embed_builder.build())
+            .await
     }
 
     pub async fn send_message_response(
@@ -131,6 +378,30 @@ impl Context {
         Ok(())
     }
 
+    /// Message only visible to the user who triggered the interaction
+    pub async fn send_ephemeral_message_response(
+        &self,
+        interaction: &Interaction,
+        content: impl Into<String>,
+    ) -> anyhow::Result<()> {
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(
+                InteractionResponseDataBuilder::new()
+                    .content(content)
+                    .flags(MessageFlags::EPHEMERAL)
+                    .build(),
+            ),
+        };
+
+        self.interaction_client()
+            .await?
+            .create_response(interaction.id, &interaction.token, &response)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn send_embed_response(
         &self,
         interaction: &Interaction,
@@ -152,4 +423,187 @@ impl Context {
 
         Ok(())
     }
+
+    pub async fn send_ephemeral_embed_response(
+        &self,
+        interaction: &Interaction,
+        embed: Embed,
+    ) -> anyhow::Result<()> {
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(
+                InteractionResponseDataBuilder::new()
+                    .embeds(vec![embed])
+                    .flags(MessageFlags::EPHEMERAL)
+                    .build(),
+            ),
+        };
+
+        self.interaction_client()
+            .await?
+            .create_response(interaction.id, &interaction.token, &response)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Message with a file attached, `data` holds the rest of it like a content or embeds
+    pub async fn send_file_response(
+        &self,
+        interaction: &Interaction,
+        data: InteractionResponseDataBuilder,
+        filename: &str,
+        file: impl Into<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(
+                data.attachments([Attachment::from_bytes(filename.to_owned(), file.into(), 0)])
+                    .build(),
+            ),
+        };
+
+        self.interaction_client()
+            .await?
+            .create_response(interaction.id, &interaction.token, &response)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Acknowledge the interaction, giving the bot 15 minutes to respond instead of 3 seconds.
+    /// The response is filled in later with the `update_*_response` methods
+    pub async fn defer_response(&self, interaction: &Interaction) -> anyhow::Result<()> {
+        let response = InteractionResponse {
+            kind: InteractionResponseType::DeferredChannelMessageWithSource,
+            data: None,
+        };
+
+        self.interaction_client()
+            .await?
+            .create_response(interaction.id, &interaction.token, &response)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_message_response(
+        &self,
+        interaction: &Interaction,
+        content: impl Into<String>,
+    ) -> anyhow::Result<()> {
+        let content = content.into();
+
+        self.interaction_client()
+            .await?
+            .update_response(&interaction.token)
+            .content(Some(&content))?
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_embed_response(
+        &self,
+        interaction: &Interaction,
+        embed: Embed,
+    ) -> anyhow::Result<()> {
+        self.interaction_client()
+            .await?
+            .update_response(&interaction.token)
+            .embeds(Some(&[embed]))?
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Compare the definitions of two command lists, ignoring the ids discord assigns and the order
+fn same_commands(a: &[Command], b: &[Command], guild: bool) -> anyhow::Result<bool> {
+    let normalize_all = |commands: &[Command]| -> anyhow::Result<Vec<Value>> {
+        let mut values = commands
+            .iter()
+            .map(|command| normalize_command(command, guild))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        values.sort_by_key(|value| value["name"].to_string());
+        Ok(values)
+    };
+
+    Ok(normalize_all(a)? == normalize_all(b)?)
+}
+
+fn normalize_command(command: &Command, guild: bool) -> anyhow::Result<Value> {
+    let mut value = serde_json::to_value(command)?;
+
+    if let Value::Object(fields) = &mut value {
+        for key in ["id", "application_id", "guild_id", "version"] {
+            fields.remove(key);
+        }
+        // Only global commands can be used in DMs, discord fills in the default for them
+        if guild {
+            fields.remove("dm_permission");
+        } else {
+            fields.entry("dm_permission").or_insert(Value::Bool(true));
+        }
+    }
+
+    remove_defaults(&mut value);
+    Ok(value)
+}
+
+/// Discord returns some unset fields with their default value, drop them on both sides
+fn remove_defaults(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|_, field| {
+                remove_defaults(field);
+                !matches!(field, Value::Null | Value::Bool(false))
+                    && field.as_array().is_none_or(|array| !array.is_empty())
+            });
+        }
+        Value::Array(items) => items.iter_mut().for_each(remove_defaults),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The commands as discord returns them once registered, with ids and in another order
+    fn registered(guild_id: Option<Id<GuildMarker>>) -> Vec<Command> {
+        let mut commands = interactions::commands();
+        for (i, command) in commands.iter_mut().enumerate() {
+            command.id = Some(Id::new(i as u64 + 1));
+            command.application_id = Some(Id::new(1));
+            command.version = Id::new(1);
+            command.guild_id = guild_id;
+            if guild_id.is_none() {
+                command.dm_permission.get_or_insert(true);
+            }
+        }
+        commands.reverse();
+        commands
+    }
+
+    #[test]
+    fn registered_commands_match_their_definitions() {
+        let commands = interactions::commands();
+
+        assert!(same_commands(&registered(None), &commands, false).unwrap());
+        assert!(same_commands(&registered(Some(Id::new(1))), &commands, true).unwrap());
+    }
+
+    #[test]
+    fn changed_commands_differ() {
+        let commands = interactions::commands();
+
+        let mut changed = registered(None);
+        changed[0].description.push_str(" and more");
+        assert!(!same_commands(&changed, &commands, false).unwrap());
+
+        let mut missing = registered(None);
+        missing.pop();
+        assert!(!same_commands(&missing, &commands, false).unwrap());
+    }
 }
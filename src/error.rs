@@ -0,0 +1,144 @@
+use std::fmt;
+
+use crate::utils::from_ms_to_minutes;
+
+/// Errors caused by how a command was used. Their message is shown to the user as is
+#[derive(Debug)]
+pub enum UserError {
+    UserNotInVoice,
+    BotNotInVoice,
+    UserNotInBotChannel,
+    NotDj,
+    NoTracksQueued,
+    EmptyQueue,
+    NothingToSkip,
+    PageOutOfBounds { max: usize },
+    NoResults,
+    LoadFailed,
+    NoSupportedLinks,
+    InvalidOptions(String),
+    UnknownCommand(String),
+    VoteExpired,
+    QueueFull { max: usize },
+    TrackTooLong { max_secs: u64 },
+    InvalidColor,
+    VoiceConnectTimeout,
+    NoFavorites,
+    NoSuchFavorite { max: usize },
+    TrackUnavailable,
+    InvalidDate,
+    SessionRunning,
+    NoSession,
+    LibraryDisabled,
+    UnknownTag(String),
+    NoSuchTagged { tag: String, max: usize },
+    NoHistoryEntry,
+    NoSceneBoard,
+    InvalidSceneBoard(String),
+    SceneBoardOutdated,
+    UnknownTable(String),
+    TableExists(String),
+    InvalidTable(String),
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UserNotInVoice => {
+                write!(f, "You need to be in a voice channel to use this command")
+            }
+            Self::BotNotInVoice => write!(f, "Im not in a voice channel"),
+            Self::UserNotInBotChannel => {
+                write!(f, "You need to be in my voice channel to use this command")
+            }
+            Self::NotDj => write!(
+                f,
+                "Only a DJ, or someone alone with me, can use this command"
+            ),
+            Self::NoTracksQueued => write!(f, "No tracks queued"),
+            Self::EmptyQueue => write!(f, "The queue is empty"),
+            Self::NothingToSkip => write!(f, "No more tracks to skip"),
+            Self::PageOutOfBounds { max } => {
+                write!(f, "Page out of bounds, use a value between 1 and {}", max)
+            }
+            Self::NoResults => write!(f, "No results found"),
+            Self::LoadFailed => write!(f, "Failed to load track"),
+            Self::NoSupportedLinks => write!(f, "No supported links found in this message"),
+            Self::InvalidOptions(reason) => write!(f, "Invalid command options: {}", reason),
+            Self::UnknownCommand(name) => write!(f, "There is no command called `{}`", name),
+            Self::VoteExpired => write!(f, "That track already ended, vote on the current one"),
+            Self::QueueFull { max } => write!(f, "The queue is full, it fits {} tracks", max),
+            Self::TrackTooLong { max_secs } => write!(
+                f,
+                "Tracks can't be longer than {}",
+                from_ms_to_minutes(max_secs.saturating_mul(1000))
+            ),
+            Self::InvalidColor => write!(f, "Colors are written in hex, like `#e04f2e`"),
+            Self::VoiceConnectTimeout => write!(
+                f,
+                "Couldn't join the voice channel in time, check that I can connect to it"
+            ),
+            Self::NoFavorites => write!(
+                f,
+                "You have no favorites yet, like a track on a now playing message"
+            ),
+            Self::NoSuchFavorite { max } => {
+                write!(f, "No such favorite, use a number between 1 and {}", max)
+            }
+            Self::TrackUnavailable => write!(f, "Couldn't find that track anymore"),
+            Self::InvalidDate => write!(f, "Dates are written like `2024-05-31`"),
+            Self::SessionRunning => write!(f, "A session is already running, end it first"),
+            Self::NoSession => write!(f, "No session is running, start one first"),
+            Self::LibraryDisabled => write!(f, "No music library is set up on this bot"),
+            Self::UnknownTag(tag) => write!(f, "No track is tagged `{}`, see /tag list", tag),
+            Self::NoSuchTagged { tag, max } => write!(
+                f,
+                "No such track in `{}`, use a number between 1 and {}",
+                tag, max
+            ),
+            Self::NoHistoryEntry => {
+                write!(f, "No such history entry, use a number shown in /history")
+            }
+            Self::NoSceneBoard => write!(
+                f,
+                "No scene board is set up for this server, ask the bot's host to add one"
+            ),
+            Self::InvalidSceneBoard(reason) => {
+                write!(f, "The scene board of this server is invalid: {}", reason)
+            }
+            Self::SceneBoardOutdated => {
+                write!(f, "This board is outdated, post a new one with /sceneboard")
+            }
+            Self::UnknownTable(name) => {
+                write!(f, "There is no table called `{}`, see /table list", name)
+            }
+            Self::TableExists(name) => write!(f, "There is already a table called `{}`", name),
+            Self::InvalidTable(reason) => write!(f, "Invalid table: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for UserError {}
+
+/// Every handler returns `anyhow::Result`, this splits the error back into
+/// something the user did wrong and something that went wrong on our side
+#[derive(Debug)]
+pub enum Error {
+    User(UserError),
+    /// Lavalink, discord http or anything else failing, only logged with an id
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<UserError>() {
+            Ok(user_err) => Self::User(user_err),
+            Err(err) => Self::Internal(err),
+        }
+    }
+}
+
+/// Short id printed in the reply and the logs, to find the error from a user report
+pub fn error_id() -> String {
+    format!("{:06x}", rand::random::<u32>() & 0xffffff)
+}
@@ -0,0 +1,93 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use dashmap::DashMap;
+use twilight_lavalink::http::Track as TwilightTrack;
+use twilight_model::id::{marker::UserMarker, Id};
+
+use crate::utils::save_json;
+
+/// Tracks each user liked, kept with their encoded track so they play again without a search.
+/// Saved as JSON after every change, like the guild settings
+pub struct FavoritesStore {
+    path: PathBuf,
+    users: DashMap<Id<UserMarker>, Vec<TwilightTrack>>,
+}
+
+impl FavoritesStore {
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+
+        let users = if path.exists() {
+            serde_json::from_str::<HashMap<Id<UserMarker>, Vec<TwilightTrack>>>(
+                &fs::read_to_string(&path)?,
+            )?
+            .into_iter()
+            .collect()
+        } else {
+            DashMap::default()
+        };
+
+        Ok(Self { path, users })
+    }
+
+    /// Favorites of the user, oldest first
+    pub fn get(&self, user_id: Id<UserMarker>) -> Vec<TwilightTrack> {
+        self.users
+            .get(&user_id)
+            .map(|tracks| tracks.clone())
+            .unwrap_or_default()
+    }
+
+    /// Add the track, or remove it if the user already liked it. Returns whether it was added
+    pub fn toggle(&self, user_id: Id<UserMarker>, track: TwilightTrack) -> anyhow::Result<bool> {
+        let added = {
+            let mut tracks = self.users.entry(user_id).or_default();
+            match tracks
+                .iter()
+                .position(|liked| liked.info.identifier == track.info.identifier)
+            {
+                Some(index) => {
+                    tracks.remove(index);
+                    false
+                }
+                None => {
+                    tracks.push(track);
+                    true
+                }
+            }
+        };
+
+        self.save()?;
+        Ok(added)
+    }
+
+    /// Remove the favorite at the index, `None` if there is none there
+    pub fn remove(
+        &self,
+        user_id: Id<UserMarker>,
+        index: usize,
+    ) -> anyhow::Result<Option<TwilightTrack>> {
+        let removed = self
+            .users
+            .get_mut(&user_id)
+            .filter(|tracks| index < tracks.len())
+            .map(|mut tracks| tracks.remove(index));
+
+        if removed.is_some() {
+            self.save()?;
+        }
+
+        Ok(removed)
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let users = self
+            .users
+            .iter()
+            .filter(|entry| !entry.value().is_empty())
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect::<HashMap<_, _>>();
+
+        save_json(&self.path, &users)
+    }
+}
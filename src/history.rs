@@ -0,0 +1,474 @@
+use std::{fs, path::Path, sync::Mutex};
+
+use dashmap::DashMap;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use twilight_lavalink::http::Track as TwilightTrack;
+use twilight_model::id::{
+    marker::{GuildMarker, UserMarker},
+    Id,
+};
+
+use crate::{track::Track, utils::unix_now};
+
+/// How a track stopped playing, stored as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    Finished,
+    /// Skipped, stopped or cut off by the bot leaving
+    Skipped,
+    Error,
+}
+
+impl EndReason {
+    /// From the reason of lavalink's track end event
+    pub fn from_lavalink(reason: &str) -> Self {
+        match reason {
+            "FINISHED" => Self::Finished,
+            "LOAD_FAILED" => Self::Error,
+            _ => Self::Skipped,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Finished => "finished",
+            Self::Skipped => "skipped",
+            Self::Error => "error",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "finished" => Some(Self::Finished),
+            "skipped" => Some(Self::Skipped),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// A track played in a guild
+pub struct Entry {
+    pub id: i64,
+    pub requester: Id<UserMarker>,
+    /// Unix seconds
+    pub started_at: i64,
+    /// `None` while it's playing, or if the bot stopped before it ended
+    pub end_reason: Option<EndReason>,
+    pub track: TwilightTrack,
+}
+
+/// Which entries to list, every field left empty matches everything
+#[derive(Default)]
+pub struct Filter {
+    pub requester: Option<Id<UserMarker>>,
+    /// `YYYY-MM-DD`, in UTC
+    pub date: Option<String>,
+}
+
+/// Every track started by the bot, kept in a SQLite database in the data directory
+pub struct History {
+    conn: Mutex<Connection>,
+    // Entry of the track playing in each guild, completed when the track ends
+    playing: DashMap<Id<GuildMarker>, i64>,
+}
+
+// Listening time is counted by the hour, the windows of the stats are off by an hour at most
+const HOUR_SECS: i64 = 60 * 60;
+
+const ENTRY_COLUMNS: &str = "id, user_id, started_at, end_reason, track";
+
+impl History {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS history (
+                id INTEGER PRIMARY KEY,
+                guild_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                started_at INTEGER NOT NULL,
+                ended_at INTEGER,
+                end_reason TEXT,
+                identifier TEXT NOT NULL,
+                title TEXT,
+                author TEXT,
+                uri TEXT NOT NULL,
+                length INTEGER NOT NULL,
+                -- Lavalink track as JSON, with the encoded track to play it again without a search
+                track TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS history_guild_started ON history (guild_id, started_at);
+            -- Seconds each user spent in the voice channel while the bot was playing, per hour.
+            -- Hours are the unix seconds they start at, so a 24 hours window is 24 buckets
+            CREATE TABLE IF NOT EXISTS listening_hours (
+                guild_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                hour INTEGER NOT NULL,
+                seconds INTEGER NOT NULL,
+                PRIMARY KEY (guild_id, user_id, hour)
+            );
+            -- Sessions running in each guild, removed when they end
+            CREATE TABLE IF NOT EXISTS sessions (
+                guild_id INTEGER PRIMARY KEY,
+                user_id INTEGER NOT NULL,
+                started_at INTEGER NOT NULL
+            );",
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            playing: DashMap::default(),
+        })
+    }
+
+    /// Returns the id of the entry, which the buttons of the now playing message refer to
+    pub fn record_start(&self, guild_id: Id<GuildMarker>, track: &Track) -> anyhow::Result<i64> {
+        let info = track.info();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO history
+                (guild_id, user_id, started_at, identifier, title, author, uri, length, track)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                guild_id.get() as i64,
+                track.requester.get() as i64,
+                unix_now(),
+                info.identifier,
+                info.title,
+                info.author,
+                info.uri,
+                info.length as i64,
+                serde_json::to_string(track.inner())?,
+            ],
+        )?;
+
+        let id = conn.last_insert_rowid();
+        self.playing.insert(guild_id, id);
+
+        Ok(id)
+    }
+
+    /// Entry of the track playing in the guild
+    pub fn playing(&self, guild_id: Id<GuildMarker>) -> Option<i64> {
+        self.playing.get(&guild_id).map(|id| *id)
+    }
+
+    pub fn record_end(&self, guild_id: Id<GuildMarker>, reason: EndReason) -> anyhow::Result<()> {
+        let Some((_, id)) = self.playing.remove(&guild_id) else {
+            return Ok(());
+        };
+
+        self.conn.lock().unwrap().execute(
+            "UPDATE history SET ended_at = ?1, end_reason = ?2 WHERE id = ?3",
+            params![unix_now(), reason.as_str(), id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn count(&self, guild_id: Id<GuildMarker>, filter: &Filter) -> anyhow::Result<usize> {
+        let count = self.conn.lock().unwrap().query_row(
+            "SELECT COUNT(*) FROM history
+            WHERE guild_id = ?1
+                AND (?2 IS NULL OR user_id = ?2)
+                AND (?3 IS NULL OR date(started_at, 'unixepoch') = date(?3))",
+            params![
+                guild_id.get() as i64,
+                filter.requester.map(|id| id.get() as i64),
+                filter.date,
+            ],
+            |row| row.get::<_, i64>(0),
+        )?;
+
+        Ok(count as usize)
+    }
+
+    /// Every entry started since the time, in the order they were played
+    pub fn played_since(
+        &self,
+        guild_id: Id<GuildMarker>,
+        since: i64,
+    ) -> anyhow::Result<Vec<Entry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM history WHERE guild_id = ?1 AND started_at >= ?2
+            ORDER BY started_at, id",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = statement
+            .query_map(params![guild_id.get() as i64, since], entry_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Start a session in the guild, `false` if one is already running
+    pub fn start_session(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+    ) -> anyhow::Result<bool> {
+        let inserted = self.conn.lock().unwrap().execute(
+            "INSERT INTO sessions (guild_id, user_id, started_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT (guild_id) DO NOTHING",
+            params![guild_id.get() as i64, user_id.get() as i64, unix_now()],
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    /// Who started the running session of the guild and when
+    pub fn session(
+        &self,
+        guild_id: Id<GuildMarker>,
+    ) -> anyhow::Result<Option<(Id<UserMarker>, i64)>> {
+        let session = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT user_id, started_at FROM sessions WHERE guild_id = ?1",
+                params![guild_id.get() as i64],
+                |row| Ok((Id::new(row.get::<_, i64>(0)? as u64), row.get::<_, i64>(1)?)),
+            )
+            .optional()?;
+
+        Ok(session)
+    }
+
+    /// End the session of the guild, returning who started it and when
+    pub fn end_session(
+        &self,
+        guild_id: Id<GuildMarker>,
+    ) -> anyhow::Result<Option<(Id<UserMarker>, i64)>> {
+        let session = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "DELETE FROM sessions WHERE guild_id = ?1 RETURNING user_id, started_at",
+                params![guild_id.get() as i64],
+                |row| Ok((Id::new(row.get::<_, i64>(0)? as u64), row.get::<_, i64>(1)?)),
+            )
+            .optional()?;
+
+        Ok(session)
+    }
+
+    /// Entries matching the filter, most recent first
+    pub fn page(
+        &self,
+        guild_id: Id<GuildMarker>,
+        filter: &Filter,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Entry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM history
+            WHERE guild_id = ?1
+                AND (?2 IS NULL OR user_id = ?2)
+                AND (?3 IS NULL OR date(started_at, 'unixepoch') = date(?3))
+            ORDER BY started_at DESC, id DESC
+            LIMIT ?4 OFFSET ?5",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = statement
+            .query_map(
+                params![
+                    guild_id.get() as i64,
+                    filter.requester.map(|id| id.get() as i64),
+                    filter.date,
+                    limit as i64,
+                    offset as i64,
+                ],
+                entry_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    pub fn get(&self, guild_id: Id<GuildMarker>, id: i64) -> anyhow::Result<Option<Entry>> {
+        let entry = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!(
+                    "SELECT {} FROM history WHERE guild_id = ?1 AND id = ?2",
+                    ENTRY_COLUMNS
+                ),
+                params![guild_id.get() as i64, id],
+                entry_from_row,
+            )
+            .optional()?;
+
+        Ok(entry)
+    }
+
+    /// Unix seconds at the start of the day (UTC), `None` if sqlite doesn't understand the date.
+    /// Checked before filtering so a typo isn't mistaken for a day without tracks
+    pub fn parse_date(&self, date: &str) -> anyhow::Result<Option<i64>> {
+        let since = self.conn.lock().unwrap().query_row(
+            "SELECT unixepoch(date(?1))",
+            params![date],
+            |row| row.get::<_, Option<i64>>(0),
+        )?;
+
+        Ok(since)
+    }
+
+    /// Count the seconds for every user listening in the guild
+    pub fn add_listening(
+        &self,
+        guild_id: Id<GuildMarker>,
+        users: &[Id<UserMarker>],
+        seconds: u64,
+    ) -> anyhow::Result<()> {
+        let hour = unix_now() / HOUR_SECS * HOUR_SECS;
+        let mut conn = self.conn.lock().unwrap();
+        let transaction = conn.transaction()?;
+        for user_id in users {
+            transaction.execute(
+                "INSERT INTO listening_hours (guild_id, user_id, hour, seconds)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (guild_id, user_id, hour)
+                DO UPDATE SET seconds = seconds + excluded.seconds",
+                params![
+                    guild_id.get() as i64,
+                    user_id.get() as i64,
+                    hour,
+                    seconds as i64
+                ],
+            )?;
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Leaderboards of the guild for the tracks started from `since` until `until` (unix seconds)
+    pub fn stats(
+        &self,
+        guild_id: Id<GuildMarker>,
+        since: i64,
+        until: Option<i64>,
+        limit: usize,
+    ) -> anyhow::Result<Stats> {
+        let conn = self.conn.lock().unwrap();
+        let range = params![guild_id.get() as i64, since, until, limit as i64];
+
+        const IN_RANGE: &str =
+            "guild_id = ?1 AND started_at >= ?2 AND (?3 IS NULL OR started_at < ?3)";
+
+        // Tracks still playing count up to now
+        let (plays, played_secs) = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(COALESCE(ended_at, unixepoch()) - started_at), 0)
+                FROM history WHERE {}",
+                IN_RANGE
+            ),
+            params![guild_id.get() as i64, since, until],
+            |row| Ok((row.get::<_, i64>(0)? as usize, row.get::<_, i64>(1)?)),
+        )?;
+
+        let top_tracks = conn
+            .prepare(&format!(
+                "SELECT MAX(title), MAX(uri), COUNT(*) AS plays FROM history WHERE {}
+                GROUP BY identifier ORDER BY plays DESC, MAX(started_at) DESC LIMIT ?4",
+                IN_RANGE
+            ))?
+            .query_map(range, |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?
+                        .unwrap_or("<Unknown>".to_string()),
+                    row.get(1)?,
+                    row.get::<_, i64>(2)? as usize,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let top_artists = conn
+            .prepare(&format!(
+                "SELECT author, COUNT(*) AS plays FROM history WHERE {} AND author IS NOT NULL
+                GROUP BY author ORDER BY plays DESC LIMIT ?4",
+                IN_RANGE
+            ))?
+            .query_map(range, |row| {
+                Ok((row.get(0)?, row.get::<_, i64>(1)? as usize))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let top_requesters = conn
+            .prepare(&format!(
+                "SELECT user_id, COUNT(*) AS plays FROM history WHERE {}
+                GROUP BY user_id ORDER BY plays DESC LIMIT ?4",
+                IN_RANGE
+            ))?
+            .query_map(range, |row| {
+                Ok((
+                    Id::new(row.get::<_, i64>(0)? as u64),
+                    row.get::<_, i64>(1)? as usize,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let top_listeners = conn
+            .prepare(
+                "SELECT user_id, SUM(seconds) AS total FROM listening_hours
+                WHERE guild_id = ?1 AND hour >= ?2 AND (?3 IS NULL OR hour < ?3)
+                GROUP BY user_id ORDER BY total DESC LIMIT ?4",
+            )?
+            .query_map(range, |row| {
+                Ok((Id::new(row.get::<_, i64>(0)? as u64), row.get(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Stats {
+            plays,
+            played_secs,
+            top_tracks,
+            top_artists,
+            top_requesters,
+            top_listeners,
+        })
+    }
+}
+
+/// What was played in a guild over a period
+pub struct Stats {
+    pub plays: usize,
+    /// Time the bot spent playing
+    pub played_secs: i64,
+    /// Title, link and number of plays
+    pub top_tracks: Vec<(String, String, usize)>,
+    pub top_artists: Vec<(String, usize)>,
+    /// Number of tracks queued by each user
+    pub top_requesters: Vec<(Id<UserMarker>, usize)>,
+    /// Seconds each user spent listening
+    pub top_listeners: Vec<(Id<UserMarker>, i64)>,
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<Entry> {
+    let track = row.get::<_, String>(4)?;
+
+    Ok(Entry {
+        id: row.get(0)?,
+        requester: Id::new(row.get::<_, i64>(1)? as u64),
+        started_at: row.get(2)?,
+        end_reason: row
+            .get::<_, Option<String>>(3)?
+            .as_deref()
+            .and_then(EndReason::parse),
+        track: serde_json::from_str(&track).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, err.into())
+        })?,
+    })
+}
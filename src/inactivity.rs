@@ -0,0 +1,177 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::{DashMap, DashSet};
+use twilight_lavalink::model::Pause;
+use twilight_model::id::{marker::GuildMarker, Id};
+use twilight_util::builder::embed::EmbedBuilder;
+
+use crate::{context::Context, interactions::leave};
+
+/// Since when each guild's voice channel has no listeners, or the bot has nothing to play.
+/// A leave timer only fires if the instant it was started with is still the stored one,
+/// so going back and forth restarts the wait instead of leaving early
+#[derive(Default)]
+pub struct Inactivity {
+    empty_since: DashMap<Id<GuildMarker>, Instant>,
+    idle_since: DashMap<Id<GuildMarker>, Instant>,
+    // Guilds paused because everyone left, only those are resumed when someone comes back
+    auto_paused: DashSet<Id<GuildMarker>>,
+}
+
+impl Inactivity {
+    pub fn clear(&self, guild_id: Id<GuildMarker>) {
+        self.empty_since.remove(&guild_id);
+        self.idle_since.remove(&guild_id);
+        self.auto_paused.remove(&guild_id);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Reason {
+    Empty,
+    Idle,
+}
+
+/// Start the leave timer (or pause, with auto-pause) if nobody is listening anymore,
+/// and stop it (or resume) if someone came back. Called on every voice state update of the guild
+pub fn check_listeners(ctx: &Arc<Context>, guild_id: Id<GuildMarker>) -> anyhow::Result<()> {
+    let Some(channel_id) = ctx.bot_voice_channel(guild_id) else {
+        return Ok(());
+    };
+
+    if !ctx.humans_in_channel(channel_id).is_empty() {
+        ctx.inactivity.empty_since.remove(&guild_id);
+
+        if ctx.inactivity.auto_paused.remove(&guild_id).is_some() {
+            if let Some(player) = ctx.lavalink.players().get(&guild_id) {
+                tracing::debug!("Resuming guild {}, someone came back", guild_id);
+                player.send(Pause::from((guild_id, false)))?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    if ctx.settings.get(guild_id).auto_pause {
+        // Paused by someone before leaving, it stays that way when they are back
+        if let Some(player) = ctx.lavalink.players().get(&guild_id) {
+            if !player.paused() {
+                tracing::debug!("Pausing guild {}, everyone left", guild_id);
+                player.send(Pause::from((guild_id, true)))?;
+                ctx.inactivity.auto_paused.insert(guild_id);
+            }
+        }
+
+        return Ok(());
+    }
+
+    // Already waiting since the last one left
+    if ctx.inactivity.empty_since.contains_key(&guild_id) {
+        return Ok(());
+    }
+
+    let since = Instant::now();
+    ctx.inactivity.empty_since.insert(guild_id, since);
+
+    let wait = Duration::from_secs(ctx.settings.get(guild_id).leave_empty_after);
+    tokio::spawn(leave_after(
+        ctx.clone(),
+        guild_id,
+        Reason::Empty,
+        since,
+        wait,
+    ));
+
+    Ok(())
+}
+
+/// The queue ended or the bot joined without anything to play
+pub fn mark_idle(ctx: &Arc<Context>, guild_id: Id<GuildMarker>) {
+    let since = Instant::now();
+    ctx.inactivity.idle_since.insert(guild_id, since);
+
+    let wait = Duration::from_secs(ctx.settings.get(guild_id).leave_idle_after);
+    tokio::spawn(leave_after(
+        ctx.clone(),
+        guild_id,
+        Reason::Idle,
+        since,
+        wait,
+    ));
+}
+
+/// A track started playing
+pub fn mark_active(ctx: &Context, guild_id: Id<GuildMarker>) {
+    ctx.inactivity.idle_since.remove(&guild_id);
+}
+
+async fn leave_after(
+    ctx: Arc<Context>,
+    guild_id: Id<GuildMarker>,
+    reason: Reason,
+    since: Instant,
+    wait: Duration,
+) {
+    tokio::time::sleep(wait).await;
+
+    let timers = match reason {
+        Reason::Empty => &ctx.inactivity.empty_since,
+        Reason::Idle => &ctx.inactivity.idle_since,
+    };
+    let still_inactive = timers.get(&guild_id).is_some_and(|start| *start == since);
+    if !still_inactive {
+        return;
+    }
+
+    if let Err(err) = leave_inactive(&ctx, guild_id, reason).await {
+        tracing::error!("Failed to leave inactive guild {}: {:?}", guild_id, err);
+    }
+}
+
+async fn leave_inactive(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    reason: Reason,
+) -> anyhow::Result<()> {
+    let settings = ctx.settings.get(guild_id);
+    if settings.stay_connected {
+        ctx.inactivity.clear(guild_id);
+        return Ok(());
+    }
+
+    // Tracks may have been queued without starting yet, e.g. joining again while playing
+    let queue_empty = ctx
+        .get_queue(guild_id)
+        .is_none_or(|queue| queue.lock().unwrap().is_empty());
+    if matches!(reason, Reason::Idle) && !queue_empty {
+        ctx.inactivity.idle_since.remove(&guild_id);
+        return Ok(());
+    }
+
+    // Voice channels have a text chat, so it's always somewhere to say goodbye
+    let Some(voice_channel_id) = ctx.bot_voice_channel(guild_id) else {
+        ctx.inactivity.clear(guild_id);
+        return Ok(());
+    };
+
+    tracing::debug!("Leaving inactive guild {}", guild_id);
+    leave::disconnect(ctx, guild_id).await?;
+
+    let description = match reason {
+        Reason::Empty => "Everyone left, so I did too",
+        Reason::Idle => "Nothing to play for a while, see you choom",
+    };
+    ctx.http_client
+        .create_message(settings.announce_channel.unwrap_or(voice_channel_id))
+        .embeds(&[EmbedBuilder::new()
+            .color(settings.embed_color)
+            .title("Left the voice channel")
+            .description(description)
+            .build()])?
+        .await?;
+
+    Ok(())
+}
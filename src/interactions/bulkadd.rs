@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_lavalink::http::{LoadType, Track as TwilightTrack};
+use twilight_model::{
+    application::interaction::{modal::ModalInteractionData, Interaction},
+    channel::message::{
+        component::{ActionRow, TextInput, TextInputStyle},
+        Component,
+    },
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    id::{marker::GuildMarker, Id},
+};
+use twilight_util::builder::InteractionResponseDataBuilder;
+
+use super::{precondition::Precondition, SlashCommand};
+use crate::{
+    context::Context,
+    lavalink::{load_tracks, search_query, MAX_CONCURRENT_LOADS},
+    utils::track_line,
+};
+
+pub const MODAL_ID: &str = "bulkadd";
+
+const ENTRIES_INPUT_ID: &str = "entries";
+
+// Upper bound of lines taken from a single modal, the rest is reported as failed
+const MAX_ENTRIES: usize = 50;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "bulkadd", desc = "Queue many links or search queries at once")]
+pub struct BulkAddCommand;
+
+impl SlashCommand for BulkAddCommand {
+    const PRECONDITIONS: &'static [Precondition] =
+        &[Precondition::BotInVoice, Precondition::UserInBotChannel];
+
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Bulkadd command by {}", author.name);
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::Modal,
+            data: Some(
+                InteractionResponseDataBuilder::new()
+                    .custom_id(MODAL_ID)
+                    .title("Add tracks")
+                    .components(vec![Component::ActionRow(ActionRow {
+                        components: vec![Component::TextInput(TextInput {
+                            custom_id: ENTRIES_INPUT_ID.to_owned(),
+                            label: "One link or search query per line".to_owned(),
+                            max_length: Some(4000),
+                            min_length: Some(1),
+                            placeholder: Some("https://youtu.be/...\nartist - song".to_owned()),
+                            required: Some(true),
+                            style: TextInputStyle::Paragraph,
+                            value: None,
+                        })],
+                    })])
+                    .build(),
+            ),
+        };
+
+        ctx.interaction_client()
+            .await?
+            .create_response(interaction.id, &interaction.token, &response)
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub async fn submit(
+    interaction: &Interaction,
+    data: &ModalInteractionData,
+    ctx: Arc<Context>,
+    _shard_id: ShardId,
+) -> anyhow::Result<()> {
+    let guild_id = interaction
+        .guild_id
+        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+    ctx.defer_response(interaction).await?;
+
+    queue_entries(interaction, data, ctx.clone(), guild_id).await
+}
+
+async fn queue_entries(
+    interaction: &Interaction,
+    data: &ModalInteractionData,
+    ctx: Arc<Context>,
+    guild_id: Id<GuildMarker>,
+) -> anyhow::Result<()> {
+    let entries = data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find(|component| component.custom_id == ENTRIES_INPUT_ID)
+        .and_then(|component| component.value.clone())
+        .unwrap_or_default();
+
+    let lines = entries
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+
+    let overflow = lines
+        .iter()
+        .skip(MAX_ENTRIES)
+        .map(|line| format!("{} (too many entries)", line))
+        .collect::<Vec<_>>();
+
+    // `buffered` keeps the results in the same order as the lines
+    let results = futures::stream::iter(lines.into_iter().take(MAX_ENTRIES))
+        .map(|line| {
+            let ctx = ctx.clone();
+            async move {
+                let result = resolve_entry(&ctx, guild_id, &line).await;
+                (line, result)
+            }
+        })
+        .buffered(MAX_CONCURRENT_LOADS)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut queued = Vec::new();
+    let mut failed = Vec::new();
+    for (line, result) in results {
+        match result {
+            Ok(tracks) => {
+                queued.extend(tracks.into_iter().map(|track| (track_line(&track), track)))
+            }
+            Err(reason) => failed.push(format!("{} ({})", line, reason)),
+        }
+    }
+    failed.extend(overflow);
+
+    ctx.enqueue_with_summary(interaction, guild_id, queued, failed)
+        .await
+}
+
+/// Load a single line, returning a short reason on failure
+async fn resolve_entry(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    line: &str,
+) -> Result<Vec<TwilightTrack>, &'static str> {
+    let loaded = match load_tracks(ctx, guild_id, search_query(line)).await {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            tracing::warn!("Failed to load {}: {}", line, err);
+            return Err("failed to load");
+        }
+    };
+
+    match loaded.load_type {
+        LoadType::TrackLoaded | LoadType::SearchResult => loaded
+            .tracks
+            .first()
+            .map(|track| vec![track.clone()])
+            .ok_or("no results"),
+        LoadType::PlaylistLoaded => Ok(loaded.tracks),
+        LoadType::NoMatches => Err("no results"),
+        _ => Err("failed to load"),
+    }
+}
@@ -0,0 +1,270 @@
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_lavalink::http::Track as TwilightTrack;
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::message::{
+        component::{Button, ButtonStyle, Component},
+        MessageFlags, ReactionType,
+    },
+    id::{marker::GuildMarker, Id},
+};
+use twilight_util::builder::{
+    embed::{EmbedBuilder, EmbedFooterBuilder},
+    InteractionResponseDataBuilder,
+};
+
+use super::{now_playing, SlashCommand};
+use crate::{context::Context, error::UserError, utils::from_ms_to_minutes, voice};
+
+/// Custom id of the like button on the now playing message, followed by the history entry id
+pub const BUTTON_ID: &str = "favorite";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "favorites", desc = "Your liked tracks", dm_permission = false)]
+pub enum FavoritesCommand {
+    #[command(name = "list")]
+    List(FavoritesList),
+    #[command(name = "play")]
+    Play(FavoritesPlay),
+    #[command(name = "remove")]
+    Remove(FavoritesRemove),
+    #[command(name = "export")]
+    Export(FavoritesExport),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "list", desc = "Show your favorites")]
+pub struct FavoritesList {
+    /// Page to look
+    #[command(min_value = 1)]
+    page: Option<i64>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "play", desc = "Queue all your favorites")]
+pub struct FavoritesPlay {
+    /// Queue them in a random order
+    shuffle: Option<bool>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "remove", desc = "Remove a track from your favorites")]
+pub struct FavoritesRemove {
+    /// Number of the track in /favorites list
+    #[command(min_value = 1)]
+    position: i64,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "export",
+    desc = "Get the links of your favorites, ready for /bulkadd"
+)]
+pub struct FavoritesExport;
+
+impl SlashCommand for FavoritesCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Favorites command by {}", author.name);
+
+        let favorites = ctx.favorites.get(author.id);
+        if favorites.is_empty() {
+            return Err(UserError::NoFavorites.into());
+        }
+
+        match self {
+            Self::List(FavoritesList { page }) => {
+                let settings = ctx.settings.get(guild_id);
+                let page = page.unwrap_or(1) as usize;
+                let num_pages = favorites.len().div_ceil(settings.queue_page_size);
+                if page > num_pages {
+                    return Err(UserError::PageOutOfBounds { max: num_pages }.into());
+                }
+
+                let begin = (page - 1) * settings.queue_page_size;
+                let description = favorites
+                    .iter()
+                    .enumerate()
+                    .skip(begin)
+                    .take(settings.queue_page_size)
+                    .map(|(index, track)| {
+                        format!(
+                            "**{}:** [{}]({}) - {}",
+                            index + 1,
+                            track.info.title.clone().unwrap_or("<Unknown>".to_string()),
+                            track.info.uri,
+                            from_ms_to_minutes(track.info.length)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                let embed = EmbedBuilder::new()
+                    .title(format!("{}'s favorites", author.name))
+                    .color(settings.embed_color)
+                    .description(description)
+                    .footer(EmbedFooterBuilder::new(format!(
+                        "Page {} out of {}",
+                        page, num_pages
+                    )))
+                    .build();
+
+                ctx.send_embed_response(interaction, embed).await
+            }
+            Self::Play(FavoritesPlay { shuffle }) => {
+                let channel_to_join = voice::channel_to_join(&ctx, guild_id, author.id)?;
+
+                ctx.defer_response(interaction).await?;
+
+                if let Some(channel_id) = channel_to_join {
+                    voice::connect(&ctx, guild_id, channel_id).await?;
+                }
+
+                let mut favorites = favorites;
+                if shuffle.unwrap_or(false) {
+                    favorites.shuffle(&mut rand::thread_rng());
+                }
+
+                queue_favorites(interaction, &ctx, guild_id, favorites).await
+            }
+            Self::Remove(FavoritesRemove { position }) => {
+                let removed = ctx
+                    .favorites
+                    .remove(author.id, position as usize - 1)?
+                    .ok_or(UserError::NoSuchFavorite {
+                        max: favorites.len(),
+                    })?;
+
+                let content = format!(
+                    "Removed **{}** from your favorites",
+                    removed.info.title.unwrap_or("<Unknown>".to_string())
+                );
+                ctx.send_ephemeral_message_response(interaction, content)
+                    .await
+            }
+            Self::Export(_) => {
+                let links = favorites
+                    .iter()
+                    .map(|track| track.info.uri.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                let data = InteractionResponseDataBuilder::new()
+                    .content(format!(
+                        "Your {} favorites, one link per line",
+                        favorites.len()
+                    ))
+                    .flags(MessageFlags::EPHEMERAL);
+                ctx.send_file_response(interaction, data, "favorites.txt", links)
+                    .await
+            }
+        }
+    }
+}
+
+/// Queue the tracks as they were saved, skipping the ones over the guild's limits
+async fn queue_favorites(
+    interaction: &Interaction,
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    favorites: Vec<TwilightTrack>,
+) -> anyhow::Result<()> {
+    let channel_id = interaction
+        .channel
+        .as_ref()
+        .map(|channel| channel.id)
+        .ok_or(anyhow::anyhow!("Invalid channel id"))?;
+    let requester = interaction
+        .author_id()
+        .ok_or(anyhow::anyhow!("No author found"))?;
+
+    let checks = ctx
+        .enqueue(guild_id, favorites, channel_id, requester)
+        .await?;
+    let skipped = checks.iter().filter(|check| check.is_err()).count();
+    let queued = checks.len() - skipped;
+
+    if queued == 0 {
+        return ctx
+            .update_message_response(
+                interaction,
+                "None of your favorites fit this server's limits",
+            )
+            .await;
+    }
+
+    let mut description = format!("Queued {} tracks", queued);
+    if skipped > 0 {
+        description.push_str(&format!(
+            "\nSkipped {} tracks over this server's limits",
+            skipped
+        ));
+    }
+    let embed = EmbedBuilder::new()
+        .title("Queued your favorites")
+        .color(ctx.settings.get(guild_id).embed_color)
+        .description(description)
+        .build();
+
+    ctx.update_embed_response(interaction, embed).await
+}
+
+/// Like button of the now playing message
+pub fn button(entry_id: i64) -> Component {
+    Component::Button(Button {
+        custom_id: Some(format!("{}:{}", BUTTON_ID, entry_id)),
+        disabled: false,
+        emoji: Some(ReactionType::Unicode {
+            name: "❤️".to_owned(),
+        }),
+        label: None,
+        style: ButtonStyle::Secondary,
+        url: None,
+    })
+}
+
+/// Like button pressed, adds the track to the user's favorites or removes it if it was there
+pub async fn press(
+    interaction: &Interaction,
+    entry_id: &str,
+    ctx: Arc<Context>,
+    _shard_id: ShardId,
+) -> anyhow::Result<()> {
+    let guild_id = interaction
+        .guild_id
+        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+    let author = interaction
+        .author()
+        .ok_or(anyhow::anyhow!("No author found"))?;
+
+    tracing::debug!("Favorite button by {}", author.name);
+
+    let track = now_playing::button_entry(&ctx, guild_id, entry_id)?.track;
+    let title = track.info.title.clone().unwrap_or("<Unknown>".to_string());
+
+    let content = if ctx.favorites.toggle(author.id, track)? {
+        format!("Added **{}** to your favorites", title)
+    } else {
+        format!("Removed **{}** from your favorites", title)
+    };
+
+    ctx.send_ephemeral_message_response(interaction, content)
+        .await
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_lavalink::http::TrackInfo;
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::message::{
+        component::{Button, ButtonStyle, Component},
+        Embed, ReactionType,
+    },
+    id::{
+        marker::{GuildMarker, UserMarker},
+        Id,
+    },
+    util::Timestamp,
+};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+
+use super::{now_playing, precondition::Precondition, SlashCommand};
+use crate::{context::Context, error::UserError, utils::from_ms_to_minutes};
+
+/// Custom id of the grab button on the now playing message, followed by the history entry id
+pub const BUTTON_ID: &str = "grab";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "grab",
+    desc = "Get the current track in your DMs",
+    dm_permission = false
+)]
+pub struct GrabCommand;
+
+impl SlashCommand for GrabCommand {
+    const PRECONDITIONS: &'static [Precondition] =
+        &[Precondition::BotInVoice, Precondition::QueueNotEmpty];
+
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Grab command by {}", author.name);
+
+        let track = ctx
+            .get_queue(guild_id)
+            .and_then(|queue| queue.lock().unwrap().peek().ok())
+            .ok_or(UserError::EmptyQueue)?;
+        let position = ctx
+            .lavalink
+            .players()
+            .get(&guild_id)
+            .map(|player| player.position() as u64);
+
+        let embed = grab_embed(&ctx, guild_id, track.info(), position)?;
+        send(&ctx, interaction, author.id, embed).await
+    }
+}
+
+/// Grab button of the now playing message
+pub fn button(entry_id: i64) -> Component {
+    Component::Button(Button {
+        custom_id: Some(format!("{}:{}", BUTTON_ID, entry_id)),
+        disabled: false,
+        emoji: Some(ReactionType::Unicode {
+            name: "📩".to_owned(),
+        }),
+        label: None,
+        style: ButtonStyle::Secondary,
+        url: None,
+    })
+}
+
+/// Grab button pressed, the position is only known if the track is still playing
+pub async fn press(
+    interaction: &Interaction,
+    entry_id: &str,
+    ctx: Arc<Context>,
+    _shard_id: ShardId,
+) -> anyhow::Result<()> {
+    let guild_id = interaction
+        .guild_id
+        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+    let author = interaction
+        .author()
+        .ok_or(anyhow::anyhow!("No author found"))?;
+
+    tracing::debug!("Grab button by {}", author.name);
+
+    let entry = now_playing::button_entry(&ctx, guild_id, entry_id)?;
+    let playing = ctx.history.playing(guild_id) == Some(entry.id);
+    let position = ctx
+        .lavalink
+        .players()
+        .get(&guild_id)
+        .filter(|_| playing)
+        .map(|player| player.position() as u64);
+
+    let embed = grab_embed(&ctx, guild_id, &entry.track.info, position)?;
+    send(&ctx, interaction, author.id, embed).await
+}
+
+fn grab_embed(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    info: &TrackInfo,
+    position: Option<u64>,
+) -> anyhow::Result<Embed> {
+    let mut embed_builder = EmbedBuilder::new()
+        .title(info.title.clone().unwrap_or("<Unknown>".to_string()))
+        .color(ctx.settings.get(guild_id).embed_color)
+        .field(
+            EmbedFieldBuilder::new(
+                "Author",
+                info.author.clone().unwrap_or("<Unknown>".to_string()),
+            )
+            .inline(),
+        )
+        .field(EmbedFieldBuilder::new("Duration", from_ms_to_minutes(info.length)).inline());
+
+    // Discord refuses anything but web links, like the path of a library track
+    if info.uri.starts_with("http") {
+        embed_builder = embed_builder.url(&info.uri);
+    }
+    if let Some(position) = position {
+        embed_builder = embed_builder
+            .field(EmbedFieldBuilder::new("Grabbed at", from_ms_to_minutes(position)).inline());
+    }
+
+    // Shown by discord in the reader's timezone
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+    Ok(embed_builder
+        .timestamp(Timestamp::from_secs(now.as_secs() as i64)?)
+        .build())
+}
+
+/// DM the embed, shown only to the user instead if their DMs are closed
+async fn send(
+    ctx: &Context,
+    interaction: &Interaction,
+    user_id: Id<UserMarker>,
+    embed: Embed,
+) -> anyhow::Result<()> {
+    let channel = ctx
+        .http_client
+        .create_private_channel(user_id)
+        .await?
+        .model()
+        .await?;
+
+    let sent = ctx
+        .http_client
+        .create_message(channel.id)
+        .embeds(std::slice::from_ref(&embed))?
+        .await;
+
+    match sent {
+        Ok(_) => {
+            ctx.send_ephemeral_message_response(interaction, "Sent it to your DMs")
+                .await
+        }
+        Err(err) => {
+            tracing::debug!("Couldn't DM {}: {}", user_id, err);
+            ctx.send_ephemeral_embed_response(interaction, embed).await
+        }
+    }
+}
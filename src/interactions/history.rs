@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::message::component::{ActionRow, Button, ButtonStyle, Component},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    id::{marker::UserMarker, Id},
+};
+use twilight_util::builder::{
+    embed::{EmbedBuilder, EmbedFooterBuilder},
+    InteractionResponseDataBuilder,
+};
+
+use super::SlashCommand;
+use crate::{
+    context::Context,
+    error::UserError,
+    history::{EndReason, Filter},
+    utils::truncate_lines,
+    voice,
+};
+
+/// Custom id of the requeue buttons under the history, followed by the id of the entry
+pub const BUTTON_ID: &str = "requeue";
+
+// Discord fits 5 buttons in a row, the page size is at most 25 so 5 rows are enough
+const BUTTONS_PER_ROW: usize = 5;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "history",
+    desc = "Show the tracks played in this server",
+    dm_permission = false
+)]
+pub struct HistoryCommand {
+    /// Page to look
+    #[command(min_value = 1)]
+    page: Option<i64>,
+    /// Only the tracks queued by this user
+    user: Option<Id<UserMarker>>,
+    /// Only the tracks played that day (UTC), like 2024-05-31
+    date: Option<String>,
+}
+
+impl SlashCommand for HistoryCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("History command by {}", author.name);
+
+        if let Some(date) = &self.date {
+            if ctx.history.parse_date(date)?.is_none() {
+                return Err(UserError::InvalidDate.into());
+            }
+        }
+
+        let filter = Filter {
+            requester: self.user,
+            date: self.date,
+        };
+
+        let count = ctx.history.count(guild_id, &filter)?;
+        if count == 0 {
+            return Err(UserError::NoResults.into());
+        }
+
+        let settings = ctx.settings.get(guild_id);
+        let page = self.page.unwrap_or(1) as usize;
+        let num_pages = count.div_ceil(settings.queue_page_size);
+        if page > num_pages {
+            return Err(UserError::PageOutOfBounds { max: num_pages }.into());
+        }
+
+        let offset = (page - 1) * settings.queue_page_size;
+        let entries = ctx
+            .history
+            .page(guild_id, &filter, offset, settings.queue_page_size)?;
+
+        // Entries are numbered by their id, which stays the same across pages and filters
+        let lines = entries
+            .iter()
+            .map(|entry| {
+                let end = match entry.end_reason {
+                    Some(EndReason::Finished) => "",
+                    Some(EndReason::Skipped) => " (skipped)",
+                    Some(EndReason::Error) => " (failed)",
+                    None => " (playing)",
+                };
+                format!(
+                    "**#{}:** [{}]({}){} - <@{}> <t:{}:R>",
+                    entry.id,
+                    entry
+                        .track
+                        .info
+                        .title
+                        .clone()
+                        .unwrap_or("<Unknown>".to_string()),
+                    entry.track.info.uri,
+                    end,
+                    entry.requester,
+                    entry.started_at
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let embed = EmbedBuilder::new()
+            .title("History")
+            .color(settings.embed_color)
+            .description(truncate_lines(&lines, 4096))
+            .footer(EmbedFooterBuilder::new(format!(
+                "Page {} out of {}, the buttons queue a track again",
+                page, num_pages
+            )))
+            .build();
+
+        // One button per entry, labeled with its number
+        let buttons = entries
+            .iter()
+            .map(|entry| {
+                Component::Button(Button {
+                    custom_id: Some(format!("{}:{}", BUTTON_ID, entry.id)),
+                    disabled: false,
+                    emoji: None,
+                    label: Some(format!("#{}", entry.id)),
+                    style: ButtonStyle::Secondary,
+                    url: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        let rows = buttons
+            .chunks(BUTTONS_PER_ROW)
+            .map(|row| {
+                Component::ActionRow(ActionRow {
+                    components: row.to_vec(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(
+                InteractionResponseDataBuilder::new()
+                    .embeds([embed])
+                    .components(rows)
+                    .build(),
+            ),
+        };
+
+        ctx.interaction_client()
+            .await?
+            .create_response(interaction.id, &interaction.token, &response)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Requeue button pressed, queues the track of the entry like /play would
+pub async fn press(
+    interaction: &Interaction,
+    entry_id: &str,
+    ctx: Arc<Context>,
+    _shard_id: ShardId,
+) -> anyhow::Result<()> {
+    let guild_id = interaction
+        .guild_id
+        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+    let author = interaction
+        .author()
+        .ok_or(anyhow::anyhow!("No author found"))?;
+
+    tracing::debug!("Requeue button by {}", author.name);
+
+    let entry_id = entry_id
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid history entry id {}", entry_id))?;
+    let entry = ctx
+        .history
+        .get(guild_id, entry_id)?
+        .ok_or(UserError::TrackUnavailable)?;
+
+    let channel_to_join = voice::channel_to_join(&ctx, guild_id, author.id)?;
+
+    ctx.defer_response(interaction).await?;
+
+    if let Some(channel_id) = channel_to_join {
+        voice::connect(&ctx, guild_id, channel_id).await?;
+    }
+
+    let channel_id = interaction
+        .channel
+        .as_ref()
+        .map(|channel| channel.id)
+        .ok_or(anyhow::anyhow!("Invalid channel id"))?;
+
+    ctx.enqueue(guild_id, vec![entry.track.clone()], channel_id, author.id)
+        .await?
+        .into_iter()
+        .collect::<Result<(), _>>()?;
+
+    let info = &entry.track.info;
+    let embed = EmbedBuilder::new()
+        .title("Track queued")
+        .color(ctx.settings.get(guild_id).embed_color)
+        .description(format!(
+            "**[{}]({})** \n By **{}**",
+            info.title.clone().unwrap_or("<Unknown>".to_string()),
+            info.uri,
+            info.author.clone().unwrap_or("<Unknown>".to_string())
+        ))
+        .build();
+
+    ctx.update_embed_response(interaction, embed).await
+}
@@ -1,57 +1,96 @@
-use crate::context::Context;
+use super::{
+    precondition::{self, Precondition},
+    SlashCommand,
+};
+use crate::{context::Context, error::UserError, inactivity, voice};
 use std::sync::Arc;
 use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
 use twilight_model::{
-    application::{
-        command::{Command, CommandType},
-        interaction::Interaction,
-    },
-    gateway::payload::outgoing::UpdateVoiceState,
+    application::interaction::Interaction,
+    id::{marker::ChannelMarker, Id},
 };
-use twilight_util::builder::command::CommandBuilder;
-
-pub const NAME: &str = "join";
 
-pub fn command() -> Command {
-    CommandBuilder::new("join", "Join a voice channel", CommandType::ChatInput).build()
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "join", desc = "Join a voice channel")]
+pub struct JoinCommand {
+    /// Voice or stage channel to join, yours by default
+    #[command(channel_types = "guild_voice guild_stage_voice")]
+    channel: Option<Id<ChannelMarker>>,
 }
 
-pub async fn run(
-    interaction: &Interaction,
-    ctx: Arc<Context>,
-    shard_id: ShardId,
-) -> anyhow::Result<()> {
-    let guild_id = interaction
-        .guild_id
-        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
-
-    let author = interaction
-        .author()
-        .ok_or(anyhow::anyhow!("No author found"))?;
-
-    tracing::debug!("Join command by {}", author.name);
-
-    let voice_channel = match ctx.cache.voice_state(author.id, guild_id) {
-        Some(vc) => vc,
-        None => {
-            return ctx
-                .send_message_response(
-                    interaction,
-                    "You need to be in a voice channel to use this command",
-                )
-                .await;
+impl SlashCommand for JoinCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Join command by {}", author.name);
+
+        let channel_id = match self.channel {
+            Some(channel_id) => channel_id,
+            None => match ctx.cache.voice_state(author.id, guild_id) {
+                Some(vc) => vc.channel_id(),
+                None => {
+                    return Err(UserError::UserNotInVoice.into());
+                }
+            },
+        };
+
+        let moved = ctx
+            .bot_voice_channel(guild_id)
+            .is_some_and(|current| current != channel_id);
+
+        // Only the people listening with the bot, or a DJ, can take it somewhere else
+        let listening = precondition::check(&ctx, interaction, Precondition::UserInBotChannel);
+        if moved && listening.is_err() {
+            precondition::check(&ctx, interaction, Precondition::Dj)?;
         }
-    };
 
-    let channel_id = voice_channel.channel_id();
+        ctx.defer_response(interaction).await?;
 
-    let sender = ctx.shard_senders.get(&shard_id).ok_or(anyhow::anyhow!(
-        "No message sender for shard id {}",
-        shard_id
-    ))?;
+        let joined = voice::connect(&ctx, guild_id, channel_id).await?;
 
-    sender.command(&UpdateVoiceState::new(guild_id, channel_id, false, false))?;
+        let mut content = if moved {
+            format!("Moved to <#{}>", channel_id)
+        } else {
+            format!("Joined <#{}>", channel_id)
+        };
+
+        // Play is sent with no_replace, so a queue that is already playing goes on as is
+        if voice::resume_kept_queue(&ctx, guild_id).await? {
+            if !moved {
+                content.push_str(", picking the queue back up");
+            }
+        } else {
+            // Nothing is queued yet, the idle timer stops once a track starts
+            inactivity::mark_idle(&ctx, guild_id);
+        }
+
+        // Moving into a stage mid-track, the next track start would be too late for the topic
+        if !matches!(joined, voice::Joined::Voice) {
+            if let Err(err) = voice::update_stage_topic(&ctx, guild_id).await {
+                tracing::warn!("Failed to set the stage topic in {}: {:?}", guild_id, err);
+            }
+        }
+
+        match joined {
+            voice::Joined::Voice | voice::Joined::StageSpeaker => {}
+            voice::Joined::StageRequestedToSpeak => content.push_str(
+                "\nI can't become a speaker on my own here, \
+                a stage moderator has to accept my request to speak",
+            ),
+        }
 
-    ctx.send_message_response(interaction, format!("Joined <#{}>", channel_id))
-        .await
+        ctx.update_message_response(interaction, content).await
+    }
 }
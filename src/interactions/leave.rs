@@ -1,67 +1,69 @@
 use std::sync::Arc;
 
 use twilight_gateway::ShardId;
-use twilight_lavalink::model::Destroy;
+use twilight_interactions::command::{CommandModel, CreateCommand};
 use twilight_model::{
-    application::{
-        command::{Command, CommandType},
-        interaction::Interaction,
-    },
+    application::interaction::Interaction,
     gateway::payload::outgoing::UpdateVoiceState,
+    id::{marker::GuildMarker, Id},
 };
-use twilight_util::builder::command::CommandBuilder;
 
+use super::{precondition::Precondition, SlashCommand};
 use crate::context::Context;
 
-pub const NAME: &str = "leave";
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "leave", desc = "Leave a voice channel")]
+pub struct LeaveCommand;
 
-pub fn command() -> Command {
-    CommandBuilder::new("leave", "Leave a voice channel", CommandType::ChatInput).build()
-}
+impl SlashCommand for LeaveCommand {
+    const PRECONDITIONS: &'static [Precondition] =
+        &[Precondition::BotInVoice, Precondition::UserInBotChannel];
+
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        tracing::debug!(
+            "Leave command by {}",
+            interaction
+                .author()
+                .ok_or(anyhow::anyhow!("No author found"))?
+                .name
+        );
 
-pub async fn run(
-    interaction: &Interaction,
-    ctx: Arc<Context>,
-    shard_id: ShardId,
-) -> anyhow::Result<()> {
-    tracing::debug!(
-        "Leave command by {}",
-        interaction
-            .author()
-            .ok_or(anyhow::anyhow!("No author found"))?
-            .name
-    );
+        let guild_id = interaction.guild_id.expect("Valid guild id");
 
-    let guild_id = interaction.guild_id.expect("Valid guild id");
+        tracing::debug!("Guild id: {}", guild_id);
 
-    tracing::debug!("Guild id: {}", guild_id);
+        disconnect(&ctx, guild_id).await?;
 
-    let bot_id = ctx.http_client.current_user().await?.model().await?.id;
-    match ctx.cache.voice_state(bot_id, guild_id) {
-        Some(vc) => vc,
-        None => {
-            return ctx
-                .send_message_response(interaction, "Im not in a voice channel")
-                .await;
-        }
-    };
+        ctx.send_message_response(interaction, "Left channel").await
+    }
+}
 
-    let player = ctx.lavalink.player(guild_id).await?;
-    player.send(Destroy::from(guild_id))?;
+/// Leave the voice channel and reset the guild's player state, also used when leaving on its own
+pub async fn disconnect(ctx: &Context, guild_id: Id<GuildMarker>) -> anyhow::Result<()> {
+    // Removed from the player manager too, so the next connection starts from a clean player
+    ctx.lavalink.players().destroy(guild_id)?;
 
-    let sender = ctx.shard_senders.get(&shard_id).ok_or(anyhow::anyhow!(
-        "No message sender for shard id {}",
-        shard_id
+    let sender = ctx.shard_sender(guild_id).ok_or(anyhow::anyhow!(
+        "No message sender for the shard of guild id {}",
+        guild_id
     ))?;
 
     sender.command(&UpdateVoiceState::new(guild_id, None, false, false))?;
 
-    // Clear queue
-    ctx.get_queue(guild_id)
-        .ok_or(anyhow::anyhow!("No queue found for guild id {}", guild_id))?
-        .lock()
-        .unwrap()
-        .clear();
+    // Clear queue and go back to the default loop mode for the next session
+    if let Some(queue_arc) = ctx.get_queue(guild_id) {
+        let mut queue = queue_arc.lock().unwrap();
+        queue.clear();
+        queue.loop_mode = ctx.settings.get(guild_id).default_loop;
+    }
+
+    ctx.skip_votes.remove(&guild_id);
+    ctx.inactivity.clear(guild_id);
 
-    ctx.send_message_response(interaction, "Left channel").await
+    Ok(())
 }
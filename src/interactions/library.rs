@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_lavalink::http::{LoadType, Track as TwilightTrack};
+use twilight_model::{
+    application::interaction::Interaction,
+    id::{marker::GuildMarker, Id},
+};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFooterBuilder};
+
+use super::SlashCommand;
+use crate::{
+    context::Context,
+    error::UserError,
+    lavalink::{load_tracks, MAX_CONCURRENT_LOADS},
+    library::LibraryTrack,
+    utils::{from_ms_to_minutes, truncate_lines},
+    voice,
+};
+
+// Results shown by /library search
+const MAX_RESULTS: usize = 10;
+
+// Files queued by a single /library play, the other matches are reported as skipped
+const MAX_FILES: usize = 50;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "library",
+    desc = "Play from the music library on the bot's machine",
+    dm_permission = false
+)]
+pub enum LibraryCommand {
+    #[command(name = "search")]
+    Search(LibrarySearch),
+    #[command(name = "play")]
+    Play(LibraryPlay),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "search", desc = "Search the library by title, artist or album")]
+pub struct LibrarySearch {
+    /// Words to look for, leave empty to list everything
+    query: Option<String>,
+    /// Only tracks of this genre
+    genre: Option<String>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "play", desc = "Queue the first match of the library by title")]
+pub struct LibraryPlay {
+    /// Words to look for in the title, artist or album
+    query: String,
+    /// Only tracks of this genre
+    genre: Option<String>,
+    /// Queue every match in title order instead of the first one
+    all: Option<bool>,
+}
+
+impl SlashCommand for LibraryCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Library command by {}", author.name);
+
+        if !ctx.library.is_enabled() {
+            return Err(UserError::LibraryDisabled.into());
+        }
+
+        match self {
+            Self::Search(LibrarySearch { query, genre }) => {
+                let results = ctx
+                    .library
+                    .search(query.as_deref().unwrap_or(""), genre.as_deref());
+                if results.is_empty() {
+                    return Err(UserError::NoResults.into());
+                }
+
+                let lines = results
+                    .iter()
+                    .take(MAX_RESULTS)
+                    .enumerate()
+                    .map(|(i, track)| format!("**{}:** {}", i + 1, describe(track)))
+                    .collect::<Vec<_>>();
+
+                let embed = EmbedBuilder::new()
+                    .title("Library")
+                    .color(ctx.settings.get(guild_id).embed_color)
+                    .description(truncate_lines(&lines, 4096))
+                    .footer(EmbedFooterBuilder::new(format!(
+                        "{} matching tracks",
+                        results.len()
+                    )))
+                    .build();
+
+                ctx.send_embed_response(interaction, embed).await
+            }
+            Self::Play(LibraryPlay { query, genre, all }) => {
+                let mut results = ctx.library.search(&query, genre.as_deref());
+                if results.is_empty() {
+                    return Err(UserError::NoResults.into());
+                }
+                let all = all.unwrap_or(false);
+                let limit = if all { MAX_FILES } else { 1 };
+                // Only the matches over the cap of `all` are reported as skipped
+                let skipped = if all {
+                    results.len().saturating_sub(limit)
+                } else {
+                    0
+                };
+                results.truncate(limit);
+
+                let channel_to_join = voice::channel_to_join(&ctx, guild_id, author.id)?;
+
+                ctx.defer_response(interaction).await?;
+
+                if let Some(channel_id) = channel_to_join {
+                    voice::connect(&ctx, guild_id, channel_id).await?;
+                }
+
+                queue_files(interaction, &ctx, guild_id, results, skipped).await
+            }
+        }
+    }
+}
+
+fn describe(track: &LibraryTrack) -> String {
+    let mut description = track.title.clone();
+    if let Some(artist) = &track.artist {
+        description.push_str(&format!(" - {}", artist));
+    }
+    if let Some(album) = &track.album {
+        description.push_str(&format!(" ({})", album));
+    }
+    description.push_str(&format!(
+        " - {}",
+        from_ms_to_minutes(track.duration.as_millis() as u64)
+    ));
+
+    description
+}
+
+/// Load the files through lavalink's local source and queue them in order
+async fn queue_files(
+    interaction: &Interaction,
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    files: Vec<LibraryTrack>,
+    skipped: usize,
+) -> anyhow::Result<()> {
+    // `buffered` keeps the results in the same order as the files
+    let results = futures::stream::iter(files)
+        .map(|file| async move {
+            let loaded = load_file(ctx, guild_id, &file).await;
+            (file, loaded)
+        })
+        .buffered(MAX_CONCURRENT_LOADS)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut tracks = Vec::new();
+    let mut failed = Vec::new();
+    for (file, loaded) in results {
+        match loaded {
+            Ok(track) => tracks.push((describe(&file), track)),
+            Err(err) => {
+                tracing::warn!("Failed to load {}: {:?}", file.path.display(), err);
+                failed.push(format!("{} (couldn't load the file)", file.title));
+            }
+        }
+    }
+
+    if skipped > 0 {
+        failed.push(format!(
+            "{} more matches (only {} files at once)",
+            skipped, MAX_FILES
+        ));
+    }
+
+    ctx.enqueue_with_summary(interaction, guild_id, tracks, failed)
+        .await
+}
+
+async fn load_file(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    file: &LibraryTrack,
+) -> anyhow::Result<TwilightTrack> {
+    let loaded = load_tracks(ctx, guild_id, file.path.to_string_lossy()).await?;
+
+    match loaded.load_type {
+        LoadType::TrackLoaded => loaded
+            .tracks
+            .into_iter()
+            .next()
+            .ok_or(anyhow::anyhow!("Lavalink loaded no track")),
+        load_type => anyhow::bail!("Unexpected load type {:?}", load_type),
+    }
+}
@@ -1,71 +1,292 @@
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
 
 use twilight_gateway::ShardId;
-use twilight_model::application::interaction::{Interaction, InteractionData, InteractionType};
+use twilight_interactions::command::{CommandInputData, CommandModel, CreateCommand};
+use twilight_model::{
+    application::{
+        command::{Command, CommandType},
+        interaction::{
+            application_command::CommandData, Interaction, InteractionData, InteractionType,
+        },
+    },
+    channel::Message,
+};
+use twilight_util::builder::command::CommandBuilder;
 
-use crate::context::Context;
+use self::precondition::Precondition;
+use crate::{
+    context::Context,
+    error::{error_id, Error, UserError},
+};
 
+pub mod bulkadd;
+pub mod favorites;
+pub mod grab;
+pub mod history;
 pub mod join;
 pub mod leave;
+pub mod library;
 pub mod lup;
 pub mod now_playing;
 pub mod pause;
+pub mod permissions;
 pub mod play;
+pub mod precondition;
 pub mod queue;
+pub mod queue_links;
 pub mod resume;
+pub mod scene;
+pub mod sceneboard;
+pub mod session;
+pub mod settings;
 pub mod shuffle;
 pub mod skip;
+pub mod stats;
+pub mod stay;
 pub mod stop;
+pub mod table;
+pub mod tag;
+pub mod voteskip;
 
+/// Slash command declared as a struct: its name, description and options come from
+/// the `CreateCommand` derive, and the options are parsed into it before `run` is called
+pub trait SlashCommand: CommandModel + CreateCommand {
+    const PRECONDITIONS: &'static [Precondition] = &[];
+    /// Subcommands everyone can use even when the guild made the command DJ-only
+    const OPEN_SUBCOMMANDS: &'static [&'static str] = &[];
+
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        shard_id: ShardId,
+    ) -> anyhow::Result<()>;
+}
+
+/// Context menu command shown on messages, it has no options and runs on the targeted message
+pub trait MessageCommand {
+    const NAME: &'static str;
+    const PRECONDITIONS: &'static [Precondition] = &[];
+
+    // Message commands can't have a description
+    fn command() -> Command {
+        CommandBuilder::new(Self::NAME, "", CommandType::Message).build()
+    }
+
+    async fn run(
+        interaction: &Interaction,
+        message: &Message,
+        ctx: Arc<Context>,
+        shard_id: ShardId,
+    ) -> anyhow::Result<()>;
+}
+
+/// Adding a command only takes declaring its type here,
+/// this generates both the list used for registration and the dispatch by name.
+/// Preconditions are checked after the options are parsed and before the handler runs
+macro_rules! commands {
+    (
+        slash: [$($slash:ty),* $(,)?],
+        message: [$($message:ty),* $(,)?] $(,)?
+    ) => {
+        pub fn commands() -> Vec<Command> {
+            vec![
+                $(<$slash as CreateCommand>::create_command().into(),)*
+                $(<$message as MessageCommand>::command(),)*
+            ]
+        }
+
+        async fn run_command(
+            interaction: &Interaction,
+            data: &CommandData,
+            ctx: Arc<Context>,
+            shard_id: ShardId,
+        ) -> anyhow::Result<()> {
+            let name = data.name.as_str();
+            $(
+                if name == <$slash as CreateCommand>::NAME {
+                    let input = CommandInputData {
+                        options: data.options.clone(),
+                        resolved: data.resolved.as_ref().map(Cow::Borrowed),
+                    };
+                    let command = <$slash as CommandModel>::from_interaction(input)
+                        .map_err(|err| UserError::InvalidOptions(err.to_string()))?;
+                    let preconditions = <$slash as SlashCommand>::PRECONDITIONS;
+                    let open = <$slash as SlashCommand>::OPEN_SUBCOMMANDS;
+                    precondition::check_all(&ctx, interaction, name, preconditions, open)?;
+                    return command.run(interaction, ctx, shard_id).await;
+                }
+            )*
+            $(
+                if name == <$message as MessageCommand>::NAME {
+                    let message = data
+                        .target_id
+                        .and_then(|target_id| {
+                            data.resolved
+                                .as_ref()?
+                                .messages
+                                .get(&target_id.cast())
+                        })
+                        .ok_or(anyhow::anyhow!("Target message was not resolved"))?;
+                    let preconditions = <$message as MessageCommand>::PRECONDITIONS;
+                    precondition::check_all(&ctx, interaction, name, preconditions, &[])?;
+                    return <$message as MessageCommand>::run(interaction, message, ctx, shard_id)
+                        .await;
+                }
+            )*
+            anyhow::bail!("Invalid command {}", name)
+        }
+    };
+}
+
+commands! {
+    slash: [
+        join::JoinCommand,
+        leave::LeaveCommand,
+        play::PlayCommand,
+        pause::PauseCommand,
+        resume::ResumeCommand,
+        stop::StopCommand,
+        skip::SkipCommand,
+        shuffle::ShuffleCommand,
+        queue::QueueCommand,
+        now_playing::NowPlayingCommand,
+        lup::LoopCommand,
+        bulkadd::BulkAddCommand,
+        permissions::PermissionsCommand,
+        settings::SettingsCommand,
+        stay::StayCommand,
+        voteskip::VoteSkipCommand,
+        favorites::FavoritesCommand,
+        grab::GrabCommand,
+        history::HistoryCommand,
+        stats::StatsCommand,
+        session::SessionCommand,
+        library::LibraryCommand,
+        tag::TagCommand,
+        scene::SceneCommand,
+        sceneboard::SceneBoardCommand,
+        table::TableCommand,
+    ],
+    message: [
+        queue_links::QueueLinksCommand,
+    ],
+}
+
+/// Run the interaction, replying with an ephemeral error message if it fails
 pub async fn handle_interaction(
     ctx: Arc<Context>,
     interaction: Interaction,
     shard_id: ShardId,
+) -> anyhow::Result<()> {
+    if let Err(err) = dispatch(&interaction, ctx.clone(), shard_id).await {
+        reply_with_error(&interaction, &ctx, err).await?;
+    }
+
+    Ok(())
+}
+
+async fn reply_with_error(
+    interaction: &Interaction,
+    ctx: &Context,
+    err: anyhow::Error,
+) -> anyhow::Result<()> {
+    let content = match Error::from(err) {
+        Error::User(err) => {
+            tracing::debug!("Interaction {} refused: {}", interaction.id, err);
+            err.to_string()
+        }
+        Error::Internal(err) => {
+            let id = error_id();
+            tracing::error!("[{}] Interaction {} failed: {:?}", id, interaction.id, err);
+            format!(
+                "Something went wrong on my end, sorry choom. Error ID: `{}`",
+                id
+            )
+        }
+    };
+
+    // Interactions that were already acknowledged (e.g. deferred) can only be edited
+    if ctx
+        .send_ephemeral_message_response(interaction, content.as_str())
+        .await
+        .is_err()
+    {
+        ctx.update_message_response(interaction, content).await?;
+    }
+
+    Ok(())
+}
+
+async fn dispatch(
+    interaction: &Interaction,
+    ctx: Arc<Context>,
+    shard_id: ShardId,
 ) -> anyhow::Result<()> {
     match interaction.kind {
         InteractionType::ApplicationCommand => {
-            if let Some(interaction_data) = &interaction.data {
-                let command_data = match interaction_data {
-                    InteractionData::ApplicationCommand(cd) => cd,
-                    _ => anyhow::bail!("Invalid type of data passed to application command"),
-                };
-                match command_data.name.as_str() {
-                    join::NAME => {
-                        join::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    leave::NAME => {
-                        leave::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    play::NAME => {
-                        play::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    pause::NAME => {
-                        pause::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    resume::NAME => {
-                        resume::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    stop::NAME => {
-                        stop::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    skip::NAME => {
-                        skip::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    shuffle::NAME => {
-                        shuffle::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    queue::NAME => {
-                        queue::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    now_playing::NAME => {
-                        now_playing::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    lup::NAME => {
-                        lup::run(&interaction, ctx.clone(), shard_id).await?;
-                    }
-                    _ => anyhow::bail!("Invalid command"),
-                };
-            }
+            let command_data = match &interaction.data {
+                Some(InteractionData::ApplicationCommand(cd)) => cd,
+                _ => anyhow::bail!("Invalid type of data passed to application command"),
+            };
+            run_command(interaction, command_data, ctx, shard_id).await?;
+        }
+        InteractionType::ModalSubmit => {
+            let modal_data = match &interaction.data {
+                Some(InteractionData::ModalSubmit(md)) => md,
+                _ => anyhow::bail!("Invalid type of data passed to modal submit"),
+            };
+            match modal_data.custom_id.as_str() {
+                bulkadd::MODAL_ID => {
+                    bulkadd::submit(interaction, modal_data, ctx.clone(), shard_id).await?;
+                }
+                _ => anyhow::bail!("Invalid modal"),
+            };
+        }
+        InteractionType::MessageComponent => {
+            let component_data = match &interaction.data {
+                Some(InteractionData::MessageComponent(cd)) => cd,
+                _ => anyhow::bail!("Invalid type of data passed to message component"),
+            };
+            // Custom ids are a prefix naming the component,
+            // optionally followed by `:` and its argument
+            let (id, argument) = component_data
+                .custom_id
+                .split_once(':')
+                .unwrap_or((component_data.custom_id.as_str(), ""));
+            match id {
+                voteskip::BUTTON_ID => {
+                    precondition::check_all(
+                        &ctx,
+                        interaction,
+                        <voteskip::VoteSkipCommand as CreateCommand>::NAME,
+                        <voteskip::VoteSkipCommand as SlashCommand>::PRECONDITIONS,
+                        &[],
+                    )?;
+                    voteskip::press(interaction, argument, ctx.clone(), shard_id).await?;
+                }
+                favorites::BUTTON_ID => {
+                    favorites::press(interaction, argument, ctx.clone(), shard_id).await?;
+                }
+                grab::BUTTON_ID => {
+                    grab::press(interaction, argument, ctx.clone(), shard_id).await?;
+                }
+                history::BUTTON_ID => {
+                    history::press(interaction, argument, ctx.clone(), shard_id).await?;
+                }
+                sceneboard::BUTTON_ID => {
+                    // Pressing a scene is DJ-only whenever posting the board is
+                    precondition::check_all(
+                        &ctx,
+                        interaction,
+                        <sceneboard::SceneBoardCommand as CreateCommand>::NAME,
+                        <sceneboard::SceneBoardCommand as SlashCommand>::PRECONDITIONS,
+                        &[],
+                    )?;
+                    sceneboard::press(interaction, argument, ctx.clone(), shard_id).await?;
+                }
+                _ => anyhow::bail!("Invalid component"),
+            };
         }
         _ => todo!("Handle other interaction types"),
     }
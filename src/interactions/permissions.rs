@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::Interaction,
+    id::{marker::RoleMarker, Id},
+};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+
+use super::{precondition::admin_permissions, SlashCommand};
+use crate::{context::Context, error::UserError};
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "permissions",
+    desc = "Configure who counts as DJ and which commands need one",
+    default_permissions = "admin_permissions",
+    dm_permission = false
+)]
+pub enum PermissionsCommand {
+    #[command(name = "show")]
+    Show(PermissionsShow),
+    #[command(name = "dj-role")]
+    DjRole(PermissionsDjRole),
+    #[command(name = "dj-only")]
+    DjOnly(PermissionsDjOnly),
+    #[command(name = "alone-is-dj")]
+    AloneIsDj(PermissionsAloneIsDj),
+    #[command(name = "vote-skip")]
+    VoteSkip(PermissionsVoteSkip),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "show", desc = "Show the current permission settings")]
+pub struct PermissionsShow;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "dj-role",
+    desc = "Set the DJ role, leave empty to use a role named dj"
+)]
+pub struct PermissionsDjRole {
+    /// Role that counts as DJ
+    role: Option<Id<RoleMarker>>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "dj-only", desc = "Make a command usable only by DJs")]
+pub struct PermissionsDjOnly {
+    /// Name of the command
+    command: String,
+    /// Whether only DJs can use it
+    enabled: bool,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "alone-is-dj",
+    desc = "Count a user alone in the voice channel with the bot as DJ"
+)]
+pub struct PermissionsAloneIsDj {
+    /// Whether being alone with the bot is enough
+    enabled: bool,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "vote-skip",
+    desc = "Set how many listeners have to vote to skip a track"
+)]
+pub struct PermissionsVoteSkip {
+    /// Percentage of the listeners in the voice channel
+    #[command(min_value = 1, max_value = 100)]
+    percent: i64,
+}
+
+impl SlashCommand for PermissionsCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Permissions command by {}", author.name);
+
+        let content = match self {
+            Self::Show(_) => {
+                let settings = ctx.settings.get(guild_id);
+
+                let dj_role = match settings.dj_role {
+                    Some(role_id) => format!("<@&{}>", role_id),
+                    None => "Any role named dj".to_owned(),
+                };
+                let dj_commands = if settings.dj_commands.is_empty() {
+                    "None".to_owned()
+                } else {
+                    settings
+                        .dj_commands
+                        .iter()
+                        .map(|command| format!("`{}`", command))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                let alone_is_dj = if settings.alone_is_dj { "Yes" } else { "No" };
+                let vote_skip = format!("{}% of the listeners", settings.vote_skip_percent);
+
+                let embed = EmbedBuilder::new()
+                    .title("Permissions")
+                    .color(settings.embed_color)
+                    .field(EmbedFieldBuilder::new("DJ role", dj_role).build())
+                    .field(EmbedFieldBuilder::new("DJ-only commands", dj_commands).build())
+                    .field(EmbedFieldBuilder::new("Alone with the bot is DJ", alone_is_dj).build())
+                    .field(EmbedFieldBuilder::new("Votes to skip", vote_skip).build())
+                    .build();
+
+                return ctx.send_embed_response(interaction, embed).await;
+            }
+            Self::DjRole(PermissionsDjRole { role }) => {
+                ctx.settings
+                    .update(guild_id, |settings| settings.dj_role = role)?;
+
+                match role {
+                    Some(role_id) => format!("<@&{}> is now the DJ role", role_id),
+                    None => "Any role named dj now counts as DJ".to_owned(),
+                }
+            }
+            Self::DjOnly(PermissionsDjOnly { command, enabled }) => {
+                let exists = super::commands()
+                    .iter()
+                    .any(|registered| registered.name == command);
+                if !exists {
+                    return Err(UserError::UnknownCommand(command).into());
+                }
+
+                ctx.settings.update(guild_id, |settings| {
+                    settings.dj_commands.retain(|name| *name != command);
+                    if enabled {
+                        settings.dj_commands.push(command.clone());
+                    }
+                })?;
+
+                if enabled {
+                    format!("Only DJs can use `{}` now", command)
+                } else {
+                    format!("Everyone can use `{}` now", command)
+                }
+            }
+            Self::AloneIsDj(PermissionsAloneIsDj { enabled }) => {
+                ctx.settings
+                    .update(guild_id, |settings| settings.alone_is_dj = enabled)?;
+
+                if enabled {
+                    "Users alone with me count as DJ now".to_owned()
+                } else {
+                    "Users alone with me need the DJ role now".to_owned()
+                }
+            }
+            Self::VoteSkip(PermissionsVoteSkip { percent }) => {
+                // Bounded by the option's min and max values
+                let percent = percent as u8;
+                ctx.settings
+                    .update(guild_id, |settings| settings.vote_skip_percent = percent)?;
+
+                format!(
+                    "Skipping now takes the votes of {}% of the listeners",
+                    percent
+                )
+            }
+        };
+
+        ctx.send_message_response(interaction, content).await
+    }
+}
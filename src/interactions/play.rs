@@ -1,141 +1,122 @@
 use std::sync::Arc;
 
-use hyper::{Body, Request};
 use twilight_gateway::ShardId;
-use twilight_lavalink::{
-    http::{LoadType, LoadedTracks},
-    model::Play,
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_lavalink::http::LoadType;
+use twilight_model::{
+    application::interaction::Interaction,
+    id::{marker::GuildMarker, Id},
 };
-use twilight_model::application::{
-    command::{Command, CommandOption, CommandOptionType, CommandType},
-    interaction::{application_command::CommandOptionValue, Interaction, InteractionData},
+use twilight_util::builder::embed::EmbedBuilder;
+
+use super::SlashCommand;
+use crate::{
+    context::Context,
+    error::UserError,
+    lavalink::{load_tracks, search_query},
+    voice,
 };
-use twilight_util::builder::{command::CommandBuilder, embed::EmbedBuilder};
-
-use crate::{context::Context, utils::check_voice_state};
-
-pub const NAME: &str = "play";
-
-pub fn command() -> Command {
-    CommandBuilder::new(
-        "play",
-        "Play a track from link or search for it on youtube",
-        CommandType::ChatInput,
-    )
-    .option(CommandOption {
-        autocomplete: Some(false),
-        channel_types: None,
-        choices: None,
-        description: "Link of track or search query to play".to_owned(),
-        description_localizations: None,
-        kind: CommandOptionType::String,
-        max_length: None,
-        max_value: None,
-        min_length: None,
-        min_value: None,
-        name: "link-or-query".to_owned(),
-        name_localizations: None,
-        options: None,
-        required: Some(true),
-    })
-    .build()
-}
-
-pub async fn run(
-    interaction: &Interaction,
-    ctx: Arc<Context>,
-    _shard_id: ShardId,
-) -> anyhow::Result<()> {
-    let guild_id = interaction
-        .guild_id
-        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
 
-    let author = interaction
-        .author()
-        .ok_or(anyhow::anyhow!("No author found"))?;
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "play",
+    desc = "Play a track from link or search for it on youtube"
+)]
+pub struct PlayCommand {
+    /// Link of track or search query to play
+    #[command(rename = "link-or-query")]
+    link_or_query: String,
+}
 
-    tracing::debug!("Play command by {}", author.name);
+impl SlashCommand for PlayCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
 
-    let bot_id = ctx.http_client.current_user().await?.model().await?.id;
-    if !check_voice_state(ctx.clone(), bot_id, guild_id) {
-        return ctx
-            .send_message_response(interaction, "Im not in a voice channel")
-            .await;
-    }
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
 
-    let options = {
-        if let Some(InteractionData::ApplicationCommand(data)) = &interaction.data {
-            &data.options
-        } else {
-            unreachable!()
-        }
-    };
+        tracing::debug!("Play command by {}", author.name);
 
-    let q = match &options[0].value {
-        CommandOptionValue::String(n) => n.clone(),
-        _ => anyhow::bail!("Option value should have been a string"),
-    };
+        // Joins the user's channel when not connected, otherwise it has to be the same channel
+        let channel_to_join = voice::channel_to_join(&ctx, guild_id, author.id)?;
 
-    let query = if q.starts_with("http") {
-        q.to_string()
-    } else {
-        format!("ytsearch:{}", q)
-    };
+        let query = search_query(&self.link_or_query);
 
-    let player = ctx.lavalink.player(guild_id).await?;
+        ctx.defer_response(interaction).await?;
 
-    let (parts, body) = twilight_lavalink::http::load_track(
-        player.node().config().address,
-        query,
-        &player.node().config().authorization,
-    )?
-    .into_parts();
+        if let Some(channel_id) = channel_to_join {
+            voice::connect(&ctx, guild_id, channel_id).await?;
+        }
 
-    let req = Request::from_parts(parts, Body::from(body));
-    let res = ctx.hyper_client.request(req).await?;
-    let res_bytes = hyper::body::to_bytes(res.into_body()).await?;
+        queue_query(interaction, ctx.clone(), guild_id, query).await
+    }
+}
 
-    let loaded = serde_json::from_slice::<LoadedTracks>(&res_bytes)?;
+async fn queue_query(
+    interaction: &Interaction,
+    ctx: Arc<Context>,
+    guild_id: Id<GuildMarker>,
+    query: String,
+) -> anyhow::Result<()> {
+    let loaded = load_tracks(&ctx, guild_id, query).await?;
 
     let channel_id = interaction
-        .channel_id
+        .channel
+        .as_ref()
+        .map(|channel| channel.id)
         .ok_or(anyhow::anyhow!("Invalid channel id"))?;
+    let requester = interaction
+        .author_id()
+        .ok_or(anyhow::anyhow!("No author found"))?;
 
-    let mut embed_builder = EmbedBuilder::new().color(0xe04f2e);
+    let settings = ctx.settings.get(guild_id);
+    let mut embed_builder = EmbedBuilder::new().color(settings.embed_color);
 
     match loaded.load_type {
         LoadType::LoadFailed => {
-            return ctx
-                .send_message_response(interaction, "Failed to load track")
-                .await
+            return Err(UserError::LoadFailed.into());
         }
         LoadType::NoMatches => {
-            return ctx
-                .send_message_response(interaction, "No results found")
-                .await;
+            return Err(UserError::NoResults.into());
         }
         LoadType::PlaylistLoaded => {
-            let queue_arc = ctx.get_or_create_queue(guild_id);
-            let queue = queue_arc.lock().unwrap();
-            for track in loaded.tracks {
-                queue.push(crate::track::Track::new(track.clone(), channel_id));
+            let checks = ctx
+                .enqueue(guild_id, loaded.tracks, channel_id, requester)
+                .await?;
+            let skipped = checks.iter().filter(|check| check.is_err()).count();
+            if skipped == checks.len() {
+                let last_err = checks.into_iter().rev().find_map(Result::err);
+                return Err(last_err.unwrap_or(UserError::NoResults).into());
             }
 
-            let first = queue.peek()?;
-
-            embed_builder = embed_builder.title("Loaded playlist").description(format!(
+            let mut description = format!(
                 "**{}**",
                 loaded.playlist_info.name.unwrap_or("<Unknown>".to_string())
-            ));
-
-            player.send(Play::from((guild_id, &first.track())))?;
+            );
+            if skipped > 0 {
+                description.push_str(&format!(
+                    "\nSkipped {} tracks over this server's limits",
+                    skipped
+                ));
+            }
+            embed_builder = embed_builder
+                .title("Loaded playlist")
+                .description(description);
         }
         LoadType::SearchResult | LoadType::TrackLoaded => {
             let track = match loaded.tracks.first() {
                 Some(t) => t,
                 None => {
                     return ctx
-                        .send_message_response(interaction, "Failed to process track")
+                        .update_message_response(interaction, "Failed to process track")
                         .await
                 }
             };
@@ -143,18 +124,18 @@ pub async fn run(
             let uri = &track.info.uri;
             let author = track.info.author.clone().unwrap_or("<Unknown>".to_string());
 
-            let queue_arc = ctx.get_or_create_queue(guild_id);
-            let queue = queue_arc.lock().unwrap();
-            queue.push(crate::track::Track::new(track.clone(), channel_id));
+            ctx.enqueue(guild_id, vec![track.clone()], channel_id, requester)
+                .await?
+                .into_iter()
+                .collect::<Result<(), _>>()?;
 
             embed_builder = embed_builder
                 .title("Track queued")
                 .description(format!("**[{}]({})** \n By **{}**", title, uri, author));
-            player.send(Play::from((guild_id, &track.track)))?;
         }
         _ => todo!(),
     }
 
-    ctx.send_embed_response(interaction, embed_builder.build())
+    ctx.update_embed_response(interaction, embed_builder.build())
         .await
 }
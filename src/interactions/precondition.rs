@@ -0,0 +1,156 @@
+use twilight_model::{
+    application::interaction::{
+        application_command::CommandOptionValue, Interaction, InteractionData,
+    },
+    guild::Permissions,
+};
+
+use crate::{context::Context, error::UserError};
+
+// Role name that counts as DJ when the guild didn't set a DJ role
+const DJ_ROLE_NAME: &str = "dj";
+
+/// Checks declared by a command, run in order before its handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// The bot is connected to a voice channel in the guild
+    BotInVoice,
+    /// The user is in the same voice channel as the bot
+    UserInBotChannel,
+    /// There is at least one track in the guild's queue
+    QueueNotEmpty,
+    /// The user has the DJ role, can manage the server, or is alone with the bot.
+    /// Checked for the commands a guild made DJ-only
+    Dj,
+}
+
+/// Runs the declared preconditions, then the DJ check if the guild made the command DJ-only.
+/// Open subcommands only read, so they skip the DJ check
+pub fn check_all(
+    ctx: &Context,
+    interaction: &Interaction,
+    command_name: &str,
+    preconditions: &[Precondition],
+    open_subcommands: &[&str],
+) -> anyhow::Result<()> {
+    for precondition in preconditions {
+        check(ctx, interaction, *precondition)?;
+    }
+
+    let open = subcommand_name(interaction).is_some_and(|name| open_subcommands.contains(&name));
+    let dj_only = interaction
+        .guild_id
+        .is_some_and(|guild_id| ctx.settings.get(guild_id).is_dj_command(command_name));
+    if dj_only && !open && !preconditions.contains(&Precondition::Dj) {
+        check(ctx, interaction, Precondition::Dj)?;
+    }
+
+    Ok(())
+}
+
+fn subcommand_name(interaction: &Interaction) -> Option<&str> {
+    let Some(InteractionData::ApplicationCommand(data)) = &interaction.data else {
+        return None;
+    };
+
+    data.options
+        .first()
+        .filter(|option| matches!(option.value, CommandOptionValue::SubCommand(_)))
+        .map(|option| option.name.as_str())
+}
+
+/// Default member permissions of the admin commands,
+/// server admins can still give them to other roles from the integration settings
+pub fn admin_permissions() -> Permissions {
+    Permissions::MANAGE_GUILD
+}
+
+/// A single check, for commands where only some subcommands need it
+pub fn check(
+    ctx: &Context,
+    interaction: &Interaction,
+    precondition: Precondition,
+) -> anyhow::Result<()> {
+    let guild_id = interaction
+        .guild_id
+        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+    let author_id = interaction
+        .author_id()
+        .ok_or(anyhow::anyhow!("No author found"))?;
+
+    match precondition {
+        Precondition::BotInVoice => {
+            if ctx.bot_voice_channel(guild_id).is_none() {
+                return Err(UserError::BotNotInVoice.into());
+            }
+        }
+        Precondition::UserInBotChannel => {
+            let bot_channel = ctx
+                .bot_voice_channel(guild_id)
+                .ok_or(UserError::BotNotInVoice)?;
+
+            let user_channel = ctx
+                .cache
+                .voice_state(author_id, guild_id)
+                .map(|voice_state| voice_state.channel_id());
+
+            if user_channel != Some(bot_channel) {
+                return Err(UserError::UserNotInBotChannel.into());
+            }
+        }
+        Precondition::QueueNotEmpty => {
+            let empty = ctx
+                .get_queue(guild_id)
+                .is_none_or(|queue| queue.lock().unwrap().is_empty());
+
+            if empty {
+                return Err(UserError::EmptyQueue.into());
+            }
+        }
+        Precondition::Dj => {
+            if !is_dj(ctx, interaction) {
+                return Err(UserError::NotDj.into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_dj(ctx: &Context, interaction: &Interaction) -> bool {
+    let (Some(guild_id), Some(member), Some(author_id)) = (
+        interaction.guild_id,
+        &interaction.member,
+        interaction.author_id(),
+    ) else {
+        return false;
+    };
+
+    if member
+        .permissions
+        .is_some_and(|permissions| permissions.contains(Permissions::MANAGE_GUILD))
+    {
+        return true;
+    }
+
+    let settings = ctx.settings.get(guild_id);
+
+    let has_dj_role = match settings.dj_role {
+        Some(dj_role) => member.roles.contains(&dj_role),
+        None => member.roles.iter().any(|role_id| {
+            ctx.cache
+                .role(*role_id)
+                .is_some_and(|role| role.name.eq_ignore_ascii_case(DJ_ROLE_NAME))
+        }),
+    };
+    if has_dj_role {
+        return true;
+    }
+
+    // Nobody else is listening, so there is nobody to bother
+    settings.alone_is_dj
+        && ctx
+            .bot_voice_channel(guild_id)
+            .is_some_and(|channel_id| ctx.humans_in_channel(channel_id) == [author_id])
+}
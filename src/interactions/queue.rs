@@ -1,166 +1,116 @@
 use std::sync::Arc;
 use twilight_gateway::ShardId;
-use twilight_model::application::{
-    command::{Command, CommandOption, CommandOptionType, CommandType},
-    interaction::{application_command::CommandOptionValue, Interaction, InteractionData},
-};
-use twilight_util::builder::{
-    command::CommandBuilder,
-    embed::{EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder},
-};
-
-use crate::{
-    context::Context,
-    utils::{check_voice_state, from_ms_to_minutes},
-};
-
-pub const NAME: &str = "queue";
-
-pub fn command() -> Command {
-    CommandBuilder::new("queue", "Shows the current queue", CommandType::ChatInput)
-        .option(CommandOption {
-            autocomplete: Some(false),
-            channel_types: None,
-            choices: None,
-            description: "Page to look".to_owned(),
-            description_localizations: None,
-            kind: CommandOptionType::Integer,
-            max_length: None,
-            max_value: None,
-            min_length: None,
-            min_value: Some(twilight_model::application::command::CommandOptionValue::Integer(1)),
-            name: "page".to_owned(),
-            name_localizations: None,
-            options: None,
-            required: Some(false),
-        })
-        .build()
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::application::interaction::Interaction;
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder};
+
+use super::{precondition::Precondition, SlashCommand};
+use crate::{context::Context, error::UserError, utils::from_ms_to_minutes};
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "queue", desc = "Shows the current queue")]
+pub struct QueueCommand {
+    /// Page to look
+    #[command(min_value = 1)]
+    page: Option<i64>,
 }
 
-pub async fn run(
-    interaction: &Interaction,
-    ctx: Arc<Context>,
-    _shard_id: ShardId,
-) -> anyhow::Result<()> {
-    tracing::debug!(
-        "Queue command by {}",
-        interaction
-            .author()
-            .ok_or(anyhow::anyhow!("No author found"))?
-            .name
-    );
-
-    let guild_id = interaction.guild_id.expect("Valid guild id");
-
-    let bot_id = ctx.http_client.current_user().await?.model().await?.id;
-    if !check_voice_state(ctx.clone(), bot_id, guild_id) {
-        return ctx
-            .send_message_response(interaction, "Im not in a voice channel")
-            .await;
-    }
-
-    // Parse option
-    let options = {
-        if let Some(InteractionData::ApplicationCommand(data)) = &interaction.data {
-            &data.options
-        } else {
-            unreachable!()
-        }
-    };
-
-    let page = if options.is_empty() {
-        1
-    } else if let CommandOptionValue::Integer(i) = options[0].value {
-        i as usize
-    } else {
-        1
-    };
-
-    let queue_arc = match ctx.get_queue(guild_id) {
-        Some(arc) => arc,
-        None => {
-            return ctx
-                .send_message_response(interaction, "No tracks queued")
-                .await;
-        }
-    };
-
-    let queue = queue_arc.lock().unwrap().current_queue();
+impl SlashCommand for QueueCommand {
+    const PRECONDITIONS: &'static [Precondition] =
+        &[Precondition::BotInVoice, Precondition::QueueNotEmpty];
+
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        tracing::debug!(
+            "Queue command by {}",
+            interaction
+                .author()
+                .ok_or(anyhow::anyhow!("No author found"))?
+                .name
+        );
+
+        let guild_id = interaction.guild_id.expect("Valid guild id");
+
+        let page = self.page.unwrap_or(1) as usize;
+
+        let queue_arc = match ctx.get_queue(guild_id) {
+            Some(arc) => arc,
+            None => {
+                return Err(UserError::NoTracksQueued.into());
+            }
+        };
 
-    let max_tracks_per_page = 10;
+        let queue = queue_arc.lock().unwrap().current_queue();
 
-    let num_pages = (queue.len() as f32 / max_tracks_per_page as f32).ceil() as usize;
+        let max_tracks_per_page = ctx.settings.get(guild_id).queue_page_size;
 
-    if queue.is_empty() {
-        return ctx
-            .send_message_response(interaction, "The queue is empty")
-            .await;
-    }
-    if page < 1 || page > num_pages {
-        return ctx
-            .send_message_response(
-                interaction,
-                format!(
-                    "Page out of bounds, use a value between 1 and {}",
-                    num_pages
-                ),
-            )
-            .await;
-    }
+        let num_pages = (queue.len() as f32 / max_tracks_per_page as f32).ceil() as usize;
 
-    let mut embed_builder = EmbedBuilder::new()
-        .title("Upcoming tracks")
-        .color(0xe04f2e)
-        .footer(EmbedFooterBuilder::new(format!(
-            "Page {} out of {}",
-            page, num_pages
-        )));
-
-    if queue.len() > max_tracks_per_page {
-        let begin = (page - 1) * max_tracks_per_page;
-        let end = begin + max_tracks_per_page;
+        if queue.is_empty() {
+            return Err(UserError::EmptyQueue.into());
+        }
+        if page < 1 || page > num_pages {
+            return Err(UserError::PageOutOfBounds { max: num_pages }.into());
+        }
 
-        let tracks_to_show = if end < queue.len() {
-            &queue[begin..end]
+        let mut embed_builder = EmbedBuilder::new()
+            .title("Upcoming tracks")
+            .color(ctx.settings.get(guild_id).embed_color)
+            .footer(EmbedFooterBuilder::new(format!(
+                "Page {} out of {}",
+                page, num_pages
+            )));
+
+        if queue.len() > max_tracks_per_page {
+            let begin = (page - 1) * max_tracks_per_page;
+            let end = begin + max_tracks_per_page;
+
+            let tracks_to_show = if end < queue.len() {
+                &queue[begin..end]
+            } else {
+                &queue[begin..queue.len()]
+            };
+
+            for track in tracks_to_show {
+                let duration = from_ms_to_minutes(track.info().length);
+                let index = queue.iter().position(|t| t == track).unwrap_or(0) + 1;
+                embed_builder = embed_builder.field(
+                    EmbedFieldBuilder::new(
+                        "\u{200b}",
+                        format!(
+                            "**{}: {} - {}**",
+                            index,
+                            track.info().title.clone().unwrap_or("UNKNOWN".to_owned()),
+                            duration
+                        ),
+                    )
+                    .build(),
+                );
+            }
         } else {
-            &queue[begin..queue.len()]
-        };
-
-        for track in tracks_to_show {
-            let duration = from_ms_to_minutes(track.info().length);
-            let index = queue.iter().position(|t| t == track).unwrap_or(0) + 1;
-            embed_builder = embed_builder.field(
-                EmbedFieldBuilder::new(
-                    "\u{200b}",
-                    format!(
-                        "**{}: {} - {}**",
-                        index,
-                        track.info().title.clone().unwrap_or("UNKNOWN".to_owned()),
-                        duration
-                    ),
-                )
-                .build(),
-            );
-        }
-    } else {
-        for track in &queue {
-            let duration = from_ms_to_minutes(track.info().length);
-            let index = queue.iter().position(|t| t == track).unwrap_or(0) + 1;
-            embed_builder = embed_builder.field(
-                EmbedFieldBuilder::new(
-                    "\u{200b}",
-                    format!(
-                        "**{}: {} - {}**",
-                        index,
-                        track.info().title.clone().unwrap_or("UNKNOWN".to_owned()),
-                        duration
-                    ),
-                )
-                .build(),
-            );
+            for track in &queue {
+                let duration = from_ms_to_minutes(track.info().length);
+                let index = queue.iter().position(|t| t == track).unwrap_or(0) + 1;
+                embed_builder = embed_builder.field(
+                    EmbedFieldBuilder::new(
+                        "\u{200b}",
+                        format!(
+                            "**{}: {} - {}**",
+                            index,
+                            track.info().title.clone().unwrap_or("UNKNOWN".to_owned()),
+                            duration
+                        ),
+                    )
+                    .build(),
+                );
+            }
         }
-    }
 
-    ctx.send_embed_response(interaction, embed_builder.build())
-        .await
+        ctx.send_embed_response(interaction, embed_builder.build())
+            .await
+    }
 }
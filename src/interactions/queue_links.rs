@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_lavalink::http::LoadType;
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::Message,
+    id::{marker::GuildMarker, Id},
+};
+use url::Url;
+
+use super::{precondition::Precondition, MessageCommand};
+use crate::{context::Context, error::UserError, lavalink::load_tracks, utils::track_line};
+
+// Hosts lavalink can load from with its default http sources
+const SUPPORTED_HOSTS: [&str; 6] = [
+    "youtube.com",
+    "youtu.be",
+    "soundcloud.com",
+    "bandcamp.com",
+    "twitch.tv",
+    "vimeo.com",
+];
+
+pub struct QueueLinksCommand;
+
+impl MessageCommand for QueueLinksCommand {
+    const NAME: &'static str = "Queue links in this message";
+    const PRECONDITIONS: &'static [Precondition] =
+        &[Precondition::BotInVoice, Precondition::UserInBotChannel];
+
+    async fn run(
+        interaction: &Interaction,
+        message: &Message,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Queue links command by {}", author.name);
+
+        let links = extract_links(message);
+        if links.is_empty() {
+            return Err(UserError::NoSupportedLinks.into());
+        }
+
+        ctx.defer_response(interaction).await?;
+
+        queue_links(interaction, ctx.clone(), guild_id, links).await
+    }
+}
+
+async fn queue_links(
+    interaction: &Interaction,
+    ctx: Arc<Context>,
+    guild_id: Id<GuildMarker>,
+    links: Vec<String>,
+) -> anyhow::Result<()> {
+    let mut queued = Vec::new();
+    let mut failed = Vec::new();
+
+    for link in links {
+        let loaded = match load_tracks(&ctx, guild_id, link.as_str()).await {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                tracing::warn!("Failed to load {}: {}", link, err);
+                failed.push(format!("{} (failed to load)", link));
+                continue;
+            }
+        };
+
+        match loaded.load_type {
+            LoadType::TrackLoaded | LoadType::SearchResult => match loaded.tracks.first() {
+                Some(track) => queued.push((track_line(track), track.clone())),
+                None => failed.push(format!("{} (no results)", link)),
+            },
+            LoadType::PlaylistLoaded => queued.extend(
+                loaded
+                    .tracks
+                    .into_iter()
+                    .map(|track| (track_line(&track), track)),
+            ),
+            LoadType::NoMatches => failed.push(format!("{} (no results)", link)),
+            _ => failed.push(format!("{} (failed to load)", link)),
+        }
+    }
+
+    ctx.enqueue_with_summary(interaction, guild_id, queued, failed)
+        .await
+}
+
+/// Collect every supported link from the message content and embeds,
+/// in order and without duplicates
+fn extract_links(message: &Message) -> Vec<String> {
+    let mut texts = vec![message.content.as_str()];
+    for embed in &message.embeds {
+        texts.extend(embed.url.as_deref());
+        texts.extend(embed.description.as_deref());
+        texts.extend(embed.fields.iter().map(|field| field.value.as_str()));
+    }
+
+    let mut links: Vec<String> = Vec::new();
+    for text in texts {
+        for candidate in text.split(|c: char| c.is_whitespace() || "<>()[]\"'|".contains(c)) {
+            if !candidate.starts_with("http") {
+                continue;
+            }
+
+            let Ok(url) = Url::parse(candidate) else {
+                continue;
+            };
+
+            if is_supported(&url) && !links.iter().any(|link| link == url.as_str()) {
+                links.push(url.to_string());
+            }
+        }
+    }
+
+    links
+}
+
+fn is_supported(url: &Url) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host,
+        None => return false,
+    };
+
+    SUPPORTED_HOSTS
+        .iter()
+        .any(|supported| host == *supported || host.ends_with(&format!(".{}", supported)))
+}
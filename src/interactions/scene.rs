@@ -0,0 +1,230 @@
+use std::{sync::Arc, time::Duration};
+
+use rand::seq::SliceRandom;
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CommandOption, CreateCommand, CreateOption};
+use twilight_lavalink::{
+    http::Track as TwilightTrack,
+    model::{Equalizer, EqualizerBand, Pause, Play, Volume},
+    player::Player,
+};
+use twilight_model::{
+    application::interaction::Interaction,
+    id::{
+        marker::{ChannelMarker, GuildMarker, UserMarker},
+        Id,
+    },
+};
+use twilight_util::builder::embed::EmbedBuilder;
+
+use super::SlashCommand;
+use crate::{
+    context::Context, error::UserError, queue::QueueLoopMode, scene_board::EqualizerPreset, tags,
+    track::Track, voice,
+};
+
+// Tracks picked from the tag's pool for one scene
+pub const SCENE_LENGTH: usize = 20;
+
+// The volume is stepped down then up again, lavalink can't fade by itself
+const FADE_STEPS: i64 = 10;
+const FADE_STEP_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "scene",
+    desc = "Switch to a shuffled selection of a tag",
+    dm_permission = false
+)]
+pub struct SceneCommand {
+    /// Tag to play, see /tag list
+    #[command(min_length = 1, max_length = 32)]
+    tag: String,
+    /// What happens to the current queue, replaced by default
+    mode: Option<SceneMode>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, CommandOption, CreateOption)]
+pub enum SceneMode {
+    #[option(name = "Replace the queue", value = "replace")]
+    Replace,
+    #[option(
+        name = "Interrupt, the queue resumes after the scene",
+        value = "interrupt"
+    )]
+    Interrupt,
+}
+
+impl SlashCommand for SceneCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Scene command by {}", author.name);
+
+        let channel_id = interaction
+            .channel
+            .as_ref()
+            .map(|channel| channel.id)
+            .ok_or(anyhow::anyhow!("Invalid channel id"))?;
+
+        let tag = tags::normalize(&self.tag);
+        let mode = self.mode.unwrap_or(SceneMode::Replace);
+        let mut pool = ctx.tags.pool(guild_id, &tag);
+        if pool.is_empty() {
+            return Err(UserError::UnknownTag(tag).into());
+        }
+        pool.shuffle(&mut rand::thread_rng());
+        pool.truncate(SCENE_LENGTH);
+
+        let channel_to_join = voice::channel_to_join(&ctx, guild_id, author.id)?;
+
+        // The fades alone take a few seconds
+        ctx.defer_response(interaction).await?;
+
+        if let Some(voice_channel_id) = channel_to_join {
+            voice::connect(&ctx, guild_id, voice_channel_id).await?;
+        }
+
+        let queued = switch_scene(
+            &ctx,
+            guild_id,
+            channel_id,
+            author.id,
+            pool,
+            mode,
+            SceneEffects::default(),
+        )
+        .await?;
+
+        let description = match mode {
+            SceneMode::Replace => format!("Playing {} tracks tagged `{}`", queued, tag),
+            SceneMode::Interrupt => format!(
+                "Playing {} tracks tagged `{}`, the queue resumes after them",
+                queued, tag
+            ),
+        };
+        let embed = EmbedBuilder::new()
+            .title(format!("Scene: {}", tag))
+            .color(ctx.settings.get(guild_id).embed_color)
+            .description(description)
+            .build();
+
+        ctx.update_embed_response(interaction, embed).await
+    }
+}
+
+/// What a scene changes besides the queue, `None` leaves it as it was
+#[derive(Default)]
+pub struct SceneEffects {
+    /// Volume the scene fades in to
+    pub volume: Option<i64>,
+    pub loop_mode: Option<QueueLoopMode>,
+    /// Filter of the scene, without one the equalizer is reset to flat
+    /// so the filter of a previous scene doesn't carry over
+    pub equalizer: Option<Vec<EqualizerBand>>,
+}
+
+/// Fade out what is playing, swap the queue for the tracks and fade them in.
+/// The bot has to be in a voice channel already. Returns the number of tracks of the scene
+pub async fn switch_scene(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    channel_id: Id<ChannelMarker>,
+    requester: Id<UserMarker>,
+    tracks: Vec<TwilightTrack>,
+    mode: SceneMode,
+    effects: SceneEffects,
+) -> anyhow::Result<usize> {
+    let queue_arc = ctx.get_or_create_queue(guild_id);
+    let settings = ctx.settings.get(guild_id);
+
+    // Picked before fading out, so a scene that can't play leaves the music alone
+    let kept = match mode {
+        SceneMode::Replace => 0,
+        SceneMode::Interrupt => queue_arc.lock().unwrap().len(),
+    };
+    let mut scene = Vec::new();
+    let mut refused = None;
+    for track in tracks {
+        match settings.check_track(kept + scene.len(), &track) {
+            Ok(()) => scene.push(Track::new(track, channel_id, requester)),
+            Err(err) => refused = Some(err),
+        }
+    }
+    if scene.is_empty() {
+        return Err(refused.unwrap_or(UserError::NoResults).into());
+    }
+    let queued = scene.len();
+
+    let player = ctx.lavalink.player(guild_id).await?;
+    let volume = effects.volume.unwrap_or(player.volume());
+    let playing = !queue_arc.lock().unwrap().is_empty() && !player.paused();
+
+    if playing {
+        fade(&player, guild_id, player.volume(), 0).await?;
+    } else {
+        player.send(Volume::from((guild_id, 0)))?;
+    }
+
+    // Changed while nothing can be heard
+    let bands = effects
+        .equalizer
+        .unwrap_or_else(|| EqualizerPreset::Flat.bands());
+    player.send(Equalizer::new(guild_id, bands))?;
+
+    let first = {
+        let mut queue = queue_arc.lock().unwrap();
+        if let Some(loop_mode) = effects.loop_mode {
+            queue.set_loop_mode(loop_mode);
+        }
+        if mode == SceneMode::Interrupt {
+            // The queue plays once the scene is over, from where the current track was cut.
+            // The position comes from the last player update, a few seconds early at worst
+            let mut rest = queue.current_queue();
+            if let Some(current) = rest.first_mut() {
+                current.start_time = u64::try_from(player.position()).ok();
+            }
+            scene.extend(rest);
+        }
+        queue.replace(scene);
+        queue.peek()?
+    };
+
+    // Replacing the track ends the current one with REPLACED, which doesn't advance the queue
+    player.send(Play::new(guild_id, first.track(), None, None, false))?;
+    if player.paused() {
+        player.send(Pause::from((guild_id, false)))?;
+    }
+
+    fade(&player, guild_id, 0, volume).await?;
+
+    Ok(queued)
+}
+
+async fn fade(
+    player: &Player,
+    guild_id: Id<GuildMarker>,
+    from: i64,
+    to: i64,
+) -> anyhow::Result<()> {
+    for step in 1..=FADE_STEPS {
+        player.send(Volume::from((
+            guild_id,
+            from + (to - from) * step / FADE_STEPS,
+        )))?;
+        tokio::time::sleep(FADE_STEP_DELAY).await;
+    }
+
+    Ok(())
+}
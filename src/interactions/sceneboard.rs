@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_lavalink::http::{LoadType, Track as TwilightTrack};
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::message::{
+        component::{ActionRow, Button, ButtonStyle, Component},
+        ReactionType,
+    },
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    id::{marker::GuildMarker, Id},
+};
+use twilight_util::builder::{embed::EmbedBuilder, InteractionResponseDataBuilder};
+
+use super::{
+    scene::{self, SceneEffects, SceneMode},
+    SlashCommand,
+};
+use crate::{
+    context::Context,
+    error::UserError,
+    lavalink::load_tracks,
+    scene_board::{BoardScene, SceneBoard},
+    tags, voice,
+};
+
+/// Custom id of the scene buttons, followed by the index of the scene in the board
+pub const BUTTON_ID: &str = "scene";
+
+const BUTTONS_PER_ROW: usize = 5;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "sceneboard",
+    desc = "Post buttons that switch to the scenes set up for this server",
+    dm_permission = false
+)]
+pub struct SceneBoardCommand;
+
+impl SlashCommand for SceneBoardCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Scene board command by {}", author.name);
+
+        let board = SceneBoard::load(&ctx.config.storage.data_dir, guild_id)?;
+
+        let buttons = board
+            .scenes
+            .iter()
+            .enumerate()
+            .map(|(index, scene)| {
+                Component::Button(Button {
+                    custom_id: Some(format!("{}:{}", BUTTON_ID, index)),
+                    disabled: false,
+                    emoji: scene
+                        .emoji
+                        .clone()
+                        .map(|name| ReactionType::Unicode { name }),
+                    label: Some(scene.label.clone()),
+                    style: ButtonStyle::Secondary,
+                    url: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        let rows = buttons
+            .chunks(BUTTONS_PER_ROW)
+            .map(|row| {
+                Component::ActionRow(ActionRow {
+                    components: row.to_vec(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let embed = EmbedBuilder::new()
+            .title("Scene board")
+            .color(ctx.settings.get(guild_id).embed_color)
+            .description("Each button switches the music to its scene right away")
+            .build();
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(
+                InteractionResponseDataBuilder::new()
+                    .embeds([embed])
+                    .components(rows)
+                    .build(),
+            ),
+        };
+
+        ctx.interaction_client()
+            .await?
+            .create_response(interaction.id, &interaction.token, &response)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Scene button pressed, the board is read again so edits apply without posting it again
+pub async fn press(
+    interaction: &Interaction,
+    index: &str,
+    ctx: Arc<Context>,
+    _shard_id: ShardId,
+) -> anyhow::Result<()> {
+    let guild_id = interaction
+        .guild_id
+        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+    let author = interaction
+        .author()
+        .ok_or(anyhow::anyhow!("No author found"))?;
+
+    tracing::debug!("Scene button by {}", author.name);
+
+    let channel_id = interaction
+        .channel
+        .as_ref()
+        .map(|channel| channel.id)
+        .ok_or(anyhow::anyhow!("Invalid channel id"))?;
+
+    let index = index
+        .parse::<usize>()
+        .map_err(|_| anyhow::anyhow!("Invalid scene index {}", index))?;
+    let mut board = SceneBoard::load(&ctx.config.storage.data_dir, guild_id)?;
+    // The file changed since the board was posted
+    if index >= board.scenes.len() {
+        return Err(UserError::SceneBoardOutdated.into());
+    }
+    let scene = board.scenes.swap_remove(index);
+
+    let channel_to_join = voice::channel_to_join(&ctx, guild_id, author.id)?;
+
+    // Loading a playlist and the fades take a few seconds
+    ctx.defer_response(interaction).await?;
+
+    if let Some(voice_channel_id) = channel_to_join {
+        voice::connect(&ctx, guild_id, voice_channel_id).await?;
+    }
+
+    let tracks = scene_tracks(&ctx, guild_id, &scene).await?;
+    let effects = SceneEffects {
+        volume: Some(
+            scene
+                .volume
+                .unwrap_or(ctx.settings.get(guild_id).default_volume) as i64,
+        ),
+        loop_mode: scene.loop_mode,
+        equalizer: Some(scene.filter.bands()),
+    };
+
+    let queued = scene::switch_scene(
+        &ctx,
+        guild_id,
+        channel_id,
+        author.id,
+        tracks,
+        SceneMode::Replace,
+        effects,
+    )
+    .await?;
+
+    ctx.update_message_response(
+        interaction,
+        format!(
+            "<@{}> switched to **{}**, {} tracks",
+            author.id, scene.label, queued
+        ),
+    )
+    .await
+}
+
+async fn scene_tracks(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    scene: &BoardScene,
+) -> anyhow::Result<Vec<TwilightTrack>> {
+    if let Some(tag) = &scene.tag {
+        let tag = tags::normalize(tag);
+        let mut pool = ctx.tags.pool(guild_id, &tag);
+        if pool.is_empty() {
+            return Err(UserError::UnknownTag(tag).into());
+        }
+        pool.shuffle(&mut rand::thread_rng());
+        pool.truncate(scene::SCENE_LENGTH);
+
+        return Ok(pool);
+    }
+
+    let playlist = scene
+        .playlist
+        .as_deref()
+        .ok_or(anyhow::anyhow!("Scene without tag nor playlist"))?;
+    let loaded = load_tracks(ctx, guild_id, playlist).await?;
+
+    let mut tracks = match loaded.load_type {
+        LoadType::LoadFailed => return Err(UserError::LoadFailed.into()),
+        LoadType::NoMatches => return Err(UserError::NoResults.into()),
+        // A search would queue every result
+        LoadType::SearchResult => loaded.tracks.into_iter().take(1).collect(),
+        _ => loaded.tracks,
+    };
+    if scene.shuffle {
+        tracks.shuffle(&mut rand::thread_rng());
+    }
+
+    Ok(tracks)
+}
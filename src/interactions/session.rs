@@ -0,0 +1,188 @@
+use std::{collections::HashMap, sync::Arc};
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::Interaction,
+    id::{marker::UserMarker, Id},
+};
+use twilight_util::builder::{
+    embed::{EmbedBuilder, EmbedFieldBuilder},
+    InteractionResponseDataBuilder,
+};
+
+use super::{
+    precondition::{self, Precondition},
+    SlashCommand,
+};
+use crate::{
+    context::Context,
+    error::UserError,
+    history::{EndReason, Entry},
+    utils::{format_duration, unix_now},
+};
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "session",
+    desc = "Bracket a game night to get its setlist",
+    dm_permission = false
+)]
+pub enum SessionCommand {
+    #[command(name = "start")]
+    Start(SessionStart),
+    #[command(name = "end")]
+    End(SessionEnd),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "start",
+    desc = "Start a session, the tracks played from now on go in its setlist"
+)]
+pub struct SessionStart;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "end", desc = "End the session and post its setlist")]
+pub struct SessionEnd;
+
+impl SlashCommand for SessionCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Session command by {}", author.name);
+
+        match self {
+            Self::Start(_) => {
+                if !ctx.history.start_session(guild_id, author.id)? {
+                    return Err(UserError::SessionRunning.into());
+                }
+
+                ctx.send_message_response(
+                    interaction,
+                    "Session started, everything played from now on goes in the setlist",
+                )
+                .await
+            }
+            Self::End(_) => {
+                // Whoever started the session ends it, or a DJ when they forgot
+                let (started_by, _) = ctx.history.session(guild_id)?.ok_or(UserError::NoSession)?;
+                if started_by != author.id {
+                    precondition::check(&ctx, interaction, Precondition::Dj)?;
+                }
+
+                let (started_by, started_at) = ctx
+                    .history
+                    .end_session(guild_id)?
+                    .ok_or(UserError::NoSession)?;
+                let length = unix_now() - started_at;
+                let entries = ctx.history.played_since(guild_id, started_at)?;
+
+                let skipped = entries
+                    .iter()
+                    .filter(|entry| entry.end_reason == Some(EndReason::Skipped))
+                    .count();
+
+                let mut requests = HashMap::<Id<UserMarker>, usize>::new();
+                for entry in &entries {
+                    *requests.entry(entry.requester).or_default() += 1;
+                }
+                let top_requester = requests
+                    .into_iter()
+                    .max_by_key(|(_, count)| *count)
+                    .map(|(user_id, count)| format!("<@{}> ({} tracks)", user_id, count))
+                    .unwrap_or("Nobody".to_owned());
+
+                let embed = EmbedBuilder::new()
+                    .title("Session ended")
+                    .color(ctx.settings.get(guild_id).embed_color)
+                    .description(format!(
+                        "Started <t:{}:f> by <@{}>, the setlist is attached",
+                        started_at, started_by
+                    ))
+                    .field(EmbedFieldBuilder::new("Length", format_duration(length)).inline())
+                    .field(EmbedFieldBuilder::new("Tracks", entries.len().to_string()).inline())
+                    .field(EmbedFieldBuilder::new("Skipped", skipped.to_string()).inline())
+                    .field(EmbedFieldBuilder::new("Most requests", top_requester).build())
+                    .build();
+
+                let setlist = setlist_markdown(&ctx, started_by, started_at, length, &entries);
+
+                ctx.send_file_response(
+                    interaction,
+                    InteractionResponseDataBuilder::new().embeds([embed]),
+                    "setlist.md",
+                    setlist,
+                )
+                .await
+            }
+        }
+    }
+}
+
+/// Table of the tracks in the order they were played, meant to be pasted in a wiki
+fn setlist_markdown(
+    ctx: &Context,
+    started_by: Id<UserMarker>,
+    started_at: i64,
+    length: i64,
+    entries: &[Entry],
+) -> String {
+    let mut setlist = format!(
+        "# Session setlist\n\nStarted by {}, lasted {}, {} tracks played\n\n",
+        ctx.user_name(started_by),
+        format_duration(length),
+        entries.len()
+    );
+
+    if entries.is_empty() {
+        setlist.push_str("Nothing was played\n");
+        return setlist;
+    }
+
+    setlist.push_str("| # | Time | Track | Requested by | |\n|---|---|---|---|---|\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let info = &entry.track.info;
+        let track = format!(
+            "[{}]({}) by {}",
+            info.title.clone().unwrap_or("<Unknown>".to_string()),
+            info.uri,
+            info.author.clone().unwrap_or("<Unknown>".to_string())
+        );
+        let ended = match entry.end_reason {
+            Some(EndReason::Finished) => "",
+            Some(EndReason::Skipped) => "skipped",
+            Some(EndReason::Error) => "failed",
+            None => "playing",
+        };
+
+        setlist.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            i + 1,
+            offset(entry.started_at - started_at),
+            // Pipes would end the cell early
+            track.replace('|', "\\|"),
+            ctx.user_name(entry.requester).replace('|', "\\|"),
+            ended
+        ));
+    }
+
+    setlist
+}
+
+/// Time since the start of the session, like `1:02:03`
+fn offset(secs: i64) -> String {
+    let secs = secs.max(0);
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
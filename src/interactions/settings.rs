@@ -0,0 +1,385 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::Interaction,
+    id::{marker::ChannelMarker, Id},
+};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+
+use super::{precondition::admin_permissions, SlashCommand};
+use crate::{context::Context, error::UserError, queue::QueueLoopMode, utils::from_ms_to_minutes};
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "settings",
+    desc = "View and change the settings of this server",
+    default_permissions = "admin_permissions",
+    dm_permission = false
+)]
+pub enum SettingsCommand {
+    #[command(name = "show")]
+    Show(SettingsShow),
+    #[command(name = "announce-channel")]
+    AnnounceChannel(SettingsAnnounceChannel),
+    #[command(name = "announce")]
+    Announce(SettingsAnnounce),
+    #[command(name = "volume")]
+    Volume(SettingsVolume),
+    #[command(name = "loop")]
+    Loop(SettingsLoop),
+    #[command(name = "max-queue")]
+    MaxQueue(SettingsMaxQueue),
+    #[command(name = "max-duration")]
+    MaxDuration(SettingsMaxDuration),
+    #[command(name = "color")]
+    Color(SettingsColor),
+    #[command(name = "page-size")]
+    PageSize(SettingsPageSize),
+    #[command(name = "leave-empty")]
+    LeaveEmpty(SettingsLeaveEmpty),
+    #[command(name = "leave-idle")]
+    LeaveIdle(SettingsLeaveIdle),
+    #[command(name = "auto-pause")]
+    AutoPause(SettingsAutoPause),
+    #[command(name = "keep-queue")]
+    KeepQueue(SettingsKeepQueue),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "show", desc = "Show the current settings")]
+pub struct SettingsShow;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "announce-channel",
+    desc = "Channel for the now playing messages, leave empty to use the channel of the command"
+)]
+pub struct SettingsAnnounceChannel {
+    /// Channel to announce in
+    #[command(channel_types = "guild_text guild_voice")]
+    channel: Option<Id<ChannelMarker>>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "announce", desc = "Send a message when a track starts")]
+pub struct SettingsAnnounce {
+    /// Whether to announce the tracks
+    enabled: bool,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "volume", desc = "Volume set when joining a voice channel")]
+pub struct SettingsVolume {
+    /// Volume, 100 is the original volume of the tracks
+    #[command(min_value = 0, max_value = 1000)]
+    volume: i64,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "loop", desc = "Loop mode of a new queue")]
+pub struct SettingsLoop {
+    /// Loop mode
+    mode: QueueLoopMode,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "max-queue",
+    desc = "Most tracks the queue can hold, leave empty for no limit"
+)]
+pub struct SettingsMaxQueue {
+    /// Number of tracks
+    #[command(min_value = 1)]
+    length: Option<i64>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "max-duration",
+    desc = "Longest track that can be queued, leave empty for no limit"
+)]
+pub struct SettingsMaxDuration {
+    /// Duration in minutes
+    #[command(min_value = 1, max_value = 1440)]
+    minutes: Option<i64>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "color", desc = "Color of the embeds sent by the bot")]
+pub struct SettingsColor {
+    /// Hex color, like #e04f2e
+    hex: String,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "page-size", desc = "Tracks shown per page of /queue")]
+pub struct SettingsPageSize {
+    /// Number of tracks, embeds can't have more than 25
+    #[command(min_value = 1, max_value = 25)]
+    size: i64,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "leave-empty",
+    desc = "How long to stay in a voice channel without listeners"
+)]
+pub struct SettingsLeaveEmpty {
+    /// Seconds to wait before leaving
+    #[command(min_value = 0, max_value = 3600)]
+    seconds: i64,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "leave-idle",
+    desc = "How long to stay in a voice channel with nothing to play"
+)]
+pub struct SettingsLeaveIdle {
+    /// Minutes to wait before leaving
+    #[command(min_value = 1, max_value = 1440)]
+    minutes: i64,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "auto-pause",
+    desc = "Pause when everyone leaves the voice channel and resume when someone is back"
+)]
+pub struct SettingsAutoPause {
+    /// Whether to pause instead of leaving
+    enabled: bool,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "keep-queue",
+    desc = "Keep the queue when someone disconnects the bot, it starts again on /join"
+)]
+pub struct SettingsKeepQueue {
+    /// Whether to keep the queue
+    enabled: bool,
+}
+
+impl SlashCommand for SettingsCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Settings command by {}", author.name);
+
+        // Option bounds are enforced by discord, so the casts below don't truncate
+        let content = match self {
+            Self::Show(_) => {
+                let settings = ctx.settings.get(guild_id);
+
+                let announce_channel = match settings.announce_channel {
+                    Some(channel_id) => format!("<#{}>", channel_id),
+                    None => "Channel of the command".to_owned(),
+                };
+                let announce = if settings.announce_now_playing {
+                    "Yes"
+                } else {
+                    "No"
+                };
+                let max_queue = match settings.max_queue_length {
+                    Some(max) => format!("{} tracks", max),
+                    None => "No limit".to_owned(),
+                };
+                let max_duration = match settings.max_track_duration {
+                    Some(max_secs) => from_ms_to_minutes(max_secs.saturating_mul(1000)),
+                    None => "No limit".to_owned(),
+                };
+                let leave = if settings.stay_connected {
+                    "Never (/247)".to_owned()
+                } else if settings.auto_pause {
+                    format!(
+                        "Pauses without listeners, {}m with nothing to play",
+                        settings.leave_idle_after / 60
+                    )
+                } else {
+                    format!(
+                        "{}s without listeners, {}m with nothing to play",
+                        settings.leave_empty_after,
+                        settings.leave_idle_after / 60
+                    )
+                };
+                let dj_role = match settings.dj_role {
+                    Some(role_id) => format!("<@&{}>", role_id),
+                    None => "Any role named dj".to_owned(),
+                };
+
+                let embed = EmbedBuilder::new()
+                    .title("Settings")
+                    .color(settings.embed_color)
+                    .field(EmbedFieldBuilder::new("Announce channel", announce_channel).inline())
+                    .field(EmbedFieldBuilder::new("Announce tracks", announce).inline())
+                    .field(
+                        EmbedFieldBuilder::new("Volume", settings.default_volume.to_string())
+                            .inline(),
+                    )
+                    .field(
+                        EmbedFieldBuilder::new("Loop", loop_mode_name(settings.default_loop))
+                            .inline(),
+                    )
+                    .field(EmbedFieldBuilder::new("Max queue length", max_queue).inline())
+                    .field(EmbedFieldBuilder::new("Max track duration", max_duration).inline())
+                    .field(
+                        EmbedFieldBuilder::new(
+                            "Embed color",
+                            format!("#{:06x}", settings.embed_color),
+                        )
+                        .inline(),
+                    )
+                    .field(
+                        EmbedFieldBuilder::new(
+                            "Queue page size",
+                            settings.queue_page_size.to_string(),
+                        )
+                        .inline(),
+                    )
+                    .field(EmbedFieldBuilder::new("Leave after", leave).inline())
+                    .field(
+                        EmbedFieldBuilder::new(
+                            "Keep queue when disconnected",
+                            if settings.keep_queue_on_disconnect {
+                                "Yes"
+                            } else {
+                                "No"
+                            },
+                        )
+                        .inline(),
+                    )
+                    .field(EmbedFieldBuilder::new("DJ role (see /permissions)", dj_role).inline())
+                    .build();
+
+                return ctx.send_embed_response(interaction, embed).await;
+            }
+            Self::AnnounceChannel(SettingsAnnounceChannel { channel }) => {
+                ctx.settings
+                    .update(guild_id, |settings| settings.announce_channel = channel)?;
+
+                match channel {
+                    Some(channel_id) => format!("Announcing tracks in <#{}>", channel_id),
+                    None => "Announcing tracks where they are queued".to_owned(),
+                }
+            }
+            Self::Announce(SettingsAnnounce { enabled }) => {
+                ctx.settings
+                    .update(guild_id, |settings| settings.announce_now_playing = enabled)?;
+
+                if enabled {
+                    "Announcing every track that starts".to_owned()
+                } else {
+                    "Not announcing tracks anymore".to_owned()
+                }
+            }
+            Self::Volume(SettingsVolume { volume }) => {
+                ctx.settings
+                    .update(guild_id, |settings| settings.default_volume = volume as u16)?;
+
+                format!("Joining voice channels at volume {}", volume)
+            }
+            Self::Loop(SettingsLoop { mode }) => {
+                ctx.settings
+                    .update(guild_id, |settings| settings.default_loop = mode)?;
+
+                format!("New queues loop mode is {}", loop_mode_name(mode))
+            }
+            Self::MaxQueue(SettingsMaxQueue { length }) => {
+                let length = length.map(|length| length as usize);
+                ctx.settings
+                    .update(guild_id, |settings| settings.max_queue_length = length)?;
+
+                match length {
+                    Some(max) => format!("The queue now fits {} tracks", max),
+                    None => "The queue length is not limited anymore".to_owned(),
+                }
+            }
+            Self::MaxDuration(SettingsMaxDuration { minutes }) => {
+                let max_secs = minutes.map(|minutes| minutes as u64 * 60);
+                ctx.settings
+                    .update(guild_id, |settings| settings.max_track_duration = max_secs)?;
+
+                match minutes {
+                    Some(minutes) => format!("Tracks can be up to {} minutes long", minutes),
+                    None => "The track duration is not limited anymore".to_owned(),
+                }
+            }
+            Self::Color(SettingsColor { hex }) => {
+                let color = u32::from_str_radix(hex.trim().trim_start_matches('#'), 16)
+                    .ok()
+                    .filter(|color| *color <= 0xffffff)
+                    .ok_or(UserError::InvalidColor)?;
+                ctx.settings
+                    .update(guild_id, |settings| settings.embed_color = color)?;
+
+                format!("Embed color set to #{:06x}", color)
+            }
+            Self::PageSize(SettingsPageSize { size }) => {
+                ctx.settings.update(guild_id, |settings| {
+                    settings.queue_page_size = size as usize
+                })?;
+
+                format!("Showing {} tracks per page of the queue", size)
+            }
+            Self::LeaveEmpty(SettingsLeaveEmpty { seconds }) => {
+                ctx.settings.update(guild_id, |settings| {
+                    settings.leave_empty_after = seconds as u64
+                })?;
+
+                format!("Leaving {} seconds after everyone leaves", seconds)
+            }
+            Self::LeaveIdle(SettingsLeaveIdle { minutes }) => {
+                ctx.settings.update(guild_id, |settings| {
+                    settings.leave_idle_after = minutes as u64 * 60
+                })?;
+
+                format!("Leaving after {} minutes with nothing to play", minutes)
+            }
+            Self::AutoPause(SettingsAutoPause { enabled }) => {
+                ctx.settings
+                    .update(guild_id, |settings| settings.auto_pause = enabled)?;
+
+                if enabled {
+                    "Pausing when everyone leaves, and resuming when someone is back".to_owned()
+                } else {
+                    "Leaving when everyone leaves".to_owned()
+                }
+            }
+            Self::KeepQueue(SettingsKeepQueue { enabled }) => {
+                ctx.settings.update(guild_id, |settings| {
+                    settings.keep_queue_on_disconnect = enabled
+                })?;
+
+                if enabled {
+                    "Keeping the queue when I get disconnected".to_owned()
+                } else {
+                    "Clearing the queue when I get disconnected".to_owned()
+                }
+            }
+        };
+
+        ctx.send_message_response(interaction, content).await
+    }
+}
+
+fn loop_mode_name(mode: QueueLoopMode) -> &'static str {
+    match mode {
+        QueueLoopMode::None => "None",
+        QueueLoopMode::LoopQueue => "Queue",
+        QueueLoopMode::LoopTrack => "Track",
+    }
+}
@@ -0,0 +1,338 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CommandOption, CreateCommand, CreateOption};
+use twilight_model::{
+    application::interaction::Interaction,
+    id::{marker::UserMarker, Id},
+};
+use twilight_util::builder::{
+    embed::{EmbedBuilder, EmbedFieldBuilder},
+    InteractionResponseDataBuilder,
+};
+
+use super::SlashCommand;
+use crate::{
+    context::Context,
+    error::UserError,
+    history::Stats,
+    utils::{format_duration, truncate_lines, unix_now},
+};
+
+// Entries of each leaderboard in the embeds, the exported recap goes further
+const LEADERBOARD_SIZE: usize = 5;
+const EXPORT_LEADERBOARD_SIZE: usize = 20;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "stats",
+    desc = "What this server listened to",
+    dm_permission = false
+)]
+pub enum StatsCommand {
+    #[command(name = "show")]
+    Show(StatsShow),
+    #[command(name = "wrapped")]
+    Wrapped(StatsWrapped),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "show", desc = "Show the top tracks, artists and listeners")]
+pub struct StatsShow {
+    /// Period to look at, the last 7 days by default
+    period: Option<StatsPeriod>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "wrapped",
+    desc = "Recap of everything played between two dates"
+)]
+pub struct StatsWrapped {
+    /// First day, like 2024-05-31 (UTC)
+    from: String,
+    /// Last day, today by default
+    to: Option<String>,
+    /// Send the recap as a Markdown file to keep
+    export: Option<bool>,
+}
+
+#[derive(Clone, Copy, CommandOption, CreateOption)]
+pub enum StatsPeriod {
+    #[option(name = "Last 24 hours", value = "day")]
+    Day,
+    #[option(name = "Last 7 days", value = "week")]
+    Week,
+    #[option(name = "Last 30 days", value = "month")]
+    Month,
+    #[option(name = "Last 365 days", value = "year")]
+    Year,
+    #[option(name = "All time", value = "all")]
+    All,
+}
+
+impl StatsPeriod {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Day => "last 24 hours",
+            Self::Week => "last 7 days",
+            Self::Month => "last 30 days",
+            Self::Year => "last 365 days",
+            Self::All => "all time",
+        }
+    }
+
+    /// Unix seconds the period starts at
+    fn since(&self) -> i64 {
+        let days = match self {
+            Self::Day => 1,
+            Self::Week => 7,
+            Self::Month => 30,
+            Self::Year => 365,
+            Self::All => return 0,
+        };
+
+        unix_now() - days * DAY_SECS
+    }
+}
+
+impl SlashCommand for StatsCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Stats command by {}", author.name);
+
+        match self {
+            Self::Show(StatsShow { period }) => {
+                let period = period.unwrap_or(StatsPeriod::Week);
+                let stats = ctx
+                    .history
+                    .stats(guild_id, period.since(), None, LEADERBOARD_SIZE)?;
+                if stats.plays == 0 {
+                    return Err(UserError::NoResults.into());
+                }
+
+                let embed = leaderboards(
+                    EmbedBuilder::new()
+                        .title(format!("Stats, {}", period.name()))
+                        .color(ctx.settings.get(guild_id).embed_color)
+                        .field(
+                            EmbedFieldBuilder::new("Tracks played", stats.plays.to_string())
+                                .inline(),
+                        )
+                        .field(
+                            EmbedFieldBuilder::new(
+                                "Time playing",
+                                format_duration(stats.played_secs),
+                            )
+                            .inline(),
+                        ),
+                    &stats,
+                )
+                .build();
+
+                ctx.send_embed_response(interaction, embed).await
+            }
+            Self::Wrapped(StatsWrapped { from, to, export }) => {
+                let since = ctx
+                    .history
+                    .parse_date(&from)?
+                    .ok_or(UserError::InvalidDate)?;
+                // The last day is included
+                let until = match &to {
+                    Some(to) => {
+                        Some(ctx.history.parse_date(to)?.ok_or(UserError::InvalidDate)? + DAY_SECS)
+                    }
+                    None => None,
+                };
+                let to = to.unwrap_or("today".to_owned());
+
+                if export.unwrap_or(false) {
+                    let stats =
+                        ctx.history
+                            .stats(guild_id, since, until, EXPORT_LEADERBOARD_SIZE)?;
+                    if stats.plays == 0 {
+                        return Err(UserError::NoResults.into());
+                    }
+
+                    let recap = wrapped_markdown(&ctx, &from, &to, &stats);
+                    return ctx
+                        .send_file_response(
+                            interaction,
+                            InteractionResponseDataBuilder::new(),
+                            "ress-wrapped.md",
+                            recap,
+                        )
+                        .await;
+                }
+
+                let stats = ctx
+                    .history
+                    .stats(guild_id, since, until, LEADERBOARD_SIZE)?;
+                if stats.plays == 0 {
+                    return Err(UserError::NoResults.into());
+                }
+
+                let embed = leaderboards(
+                    EmbedBuilder::new()
+                        .title("Ress Wrapped")
+                        .color(ctx.settings.get(guild_id).embed_color)
+                        .description(highlights(&stats, &from, &to, |user_id| {
+                            format!("<@{}>", user_id)
+                        })),
+                    &stats,
+                )
+                .build();
+
+                ctx.send_embed_response(interaction, embed).await
+            }
+        }
+    }
+}
+
+fn leaderboards(embed_builder: EmbedBuilder, stats: &Stats) -> EmbedBuilder {
+    let tracks = stats
+        .top_tracks
+        .iter()
+        .enumerate()
+        .map(|(i, (title, uri, plays))| format!("**{}:** [{}]({}) - {}", i + 1, title, uri, plays))
+        .collect::<Vec<_>>();
+    let artists = stats
+        .top_artists
+        .iter()
+        .enumerate()
+        .map(|(i, (artist, plays))| format!("**{}:** {} - {}", i + 1, artist, plays))
+        .collect::<Vec<_>>();
+    let requesters = stats
+        .top_requesters
+        .iter()
+        .enumerate()
+        .map(|(i, (user_id, plays))| format!("**{}:** <@{}> - {}", i + 1, user_id, plays))
+        .collect::<Vec<_>>();
+    let listeners = stats
+        .top_listeners
+        .iter()
+        .enumerate()
+        .map(|(i, (user_id, secs))| {
+            format!("**{}:** <@{}> - {}", i + 1, user_id, format_duration(*secs))
+        })
+        .collect::<Vec<_>>();
+
+    [
+        ("Top tracks", tracks),
+        ("Top artists", artists),
+        ("Top requesters", requesters),
+        ("Time listening", listeners),
+    ]
+    .into_iter()
+    .fold(embed_builder, |embed_builder, (name, lines)| {
+        let value = if lines.is_empty() {
+            "Nothing yet".to_owned()
+        } else {
+            truncate_lines(&lines, 1024)
+        };
+        embed_builder.field(EmbedFieldBuilder::new(name, value).build())
+    })
+}
+
+/// Opening lines of the recap, users are written by `user` (mentions or names)
+fn highlights(
+    stats: &Stats,
+    from: &str,
+    to: &str,
+    user: impl Fn(Id<UserMarker>) -> String,
+) -> String {
+    let mut lines = vec![
+        format!("From {} to {}", from, to),
+        format!(
+            "**{}** tracks, **{}** of music",
+            stats.plays,
+            format_duration(stats.played_secs)
+        ),
+    ];
+
+    if let Some((title, uri, plays)) = stats.top_tracks.first() {
+        lines.push(format!(
+            "Track of the campaign: [{}]({}), played {} times",
+            title, uri, plays
+        ));
+    }
+    if let Some((artist, plays)) = stats.top_artists.first() {
+        lines.push(format!("Most played artist: {} ({} tracks)", artist, plays));
+    }
+    if let Some((user_id, plays)) = stats.top_requesters.first() {
+        lines.push(format!(
+            "Top DJ: {} with {} tracks queued",
+            user(*user_id),
+            plays
+        ));
+    }
+    if let Some((user_id, secs)) = stats.top_listeners.first() {
+        lines.push(format!(
+            "Most dedicated listener: {} with {}",
+            user(*user_id),
+            format_duration(*secs)
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Mentions don't render outside of discord, so the file uses the names the cache knows
+fn wrapped_markdown(ctx: &Context, from: &str, to: &str, stats: &Stats) -> String {
+    let name = |user_id: Id<UserMarker>| ctx.user_name(user_id);
+
+    // Trailing spaces keep each highlight on its own line in markdown
+    let mut recap = format!(
+        "# Ress Wrapped\n\n{}\n",
+        highlights(stats, from, to, name).replace('\n', "  \n")
+    );
+
+    recap.push_str("\n## Top tracks\n\n");
+    for (i, (title, uri, plays)) in stats.top_tracks.iter().enumerate() {
+        recap.push_str(&format!(
+            "{}. [{}]({}) - {} plays\n",
+            i + 1,
+            title,
+            uri,
+            plays
+        ));
+    }
+    recap.push_str("\n## Top artists\n\n");
+    for (i, (artist, plays)) in stats.top_artists.iter().enumerate() {
+        recap.push_str(&format!("{}. {} - {} plays\n", i + 1, artist, plays));
+    }
+    recap.push_str("\n## Top requesters\n\n");
+    for (i, (user_id, plays)) in stats.top_requesters.iter().enumerate() {
+        recap.push_str(&format!(
+            "{}. {} - {} tracks\n",
+            i + 1,
+            name(*user_id),
+            plays
+        ));
+    }
+    recap.push_str("\n## Time listening\n\n");
+    for (i, (user_id, secs)) in stats.top_listeners.iter().enumerate() {
+        recap.push_str(&format!(
+            "{}. {} - {}\n",
+            i + 1,
+            name(*user_id),
+            format_duration(*secs)
+        ));
+    }
+
+    recap
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::application::interaction::Interaction;
+
+use super::{precondition::admin_permissions, SlashCommand};
+use crate::context::Context;
+
+// The module is called stay cause identifiers can't start with a number
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "247",
+    desc = "Stay in the voice channel even when it's empty or there is nothing to play",
+    default_permissions = "admin_permissions",
+    dm_permission = false
+)]
+pub struct StayCommand {
+    /// Whether to stay connected
+    enabled: bool,
+}
+
+impl SlashCommand for StayCommand {
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("247 command by {}", author.name);
+
+        ctx.settings
+            .update(guild_id, |settings| settings.stay_connected = self.enabled)?;
+
+        let content = if self.enabled {
+            "Staying in the voice channel 24/7"
+        } else {
+            "Leaving the voice channel when it's empty or idle"
+        };
+
+        ctx.send_message_response(interaction, content).await
+    }
+}
@@ -0,0 +1,650 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use hyper::{Body, Request};
+use rand::{seq::SliceRandom, Rng};
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CommandOption, CreateCommand, CreateOption};
+use twilight_lavalink::http::{LoadType, Track as TwilightTrack};
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::Attachment,
+    id::{marker::GuildMarker, Id},
+};
+use twilight_util::builder::{
+    embed::{EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder},
+    InteractionResponseDataBuilder,
+};
+
+use super::{
+    scene::{self, SceneEffects, SceneMode},
+    SlashCommand,
+};
+use crate::{
+    context::Context,
+    error::{Error, UserError},
+    lavalink::{load_tracks, search_query},
+    tables::{self, Roll, Table, TableResult},
+    tags,
+    utils::truncate_lines,
+    voice,
+};
+
+// Imported files are a few tables of text, anything bigger is a mistake
+const MAX_IMPORT_SIZE: u64 = 64 * 1024;
+
+// Same limit as the name option of the commands
+const MAX_NAME_LEN: usize = 32;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "table",
+    desc = "Random tables that roll a track",
+    dm_permission = false
+)]
+pub enum TableCommand {
+    #[command(name = "roll")]
+    Roll(TableRoll),
+    #[command(name = "list")]
+    List(TableList),
+    #[command(name = "show")]
+    Show(TableShow),
+    #[command(name = "create")]
+    Create(TableCreate),
+    #[command(name = "set")]
+    Set(TableSet),
+    #[command(name = "remove")]
+    Remove(TableRemove),
+    #[command(name = "delete")]
+    Delete(TableDelete),
+    #[command(name = "import")]
+    Import(TableImport),
+    #[command(name = "export")]
+    Export(TableExport),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "roll", desc = "Roll on a table and play its result")]
+pub struct TableRoll {
+    /// Table to roll on
+    #[command(min_length = 1, max_length = 32)]
+    name: String,
+    /// What happens to the queue, the track is added at the end by default
+    mode: Option<RollMode>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "list", desc = "Show the tables of this server")]
+pub struct TableList;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "show", desc = "Show the results of a table")]
+pub struct TableShow {
+    /// Table to show
+    #[command(min_length = 1, max_length = 32)]
+    name: String,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "create", desc = "Create an empty table")]
+pub struct TableCreate {
+    /// Name of the table, like night-market
+    #[command(min_length = 1, max_length = 32)]
+    name: String,
+    /// Sides of the die, like 6 or 10
+    #[command(min_value = 2, max_value = 100)]
+    sides: i64,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "set",
+    desc = "Set the result of a roll, replacing the ones it overlaps"
+)]
+pub struct TableSet {
+    /// Table to change
+    #[command(min_length = 1, max_length = 32)]
+    name: String,
+    /// Die results it covers, like 3 or 1-2
+    roll: String,
+    /// What is read out when it comes up
+    #[command(max_length = 1000)]
+    text: String,
+    /// Play a random track of this tag
+    #[command(max_length = 32)]
+    tag: Option<String>,
+    /// Play this link or search instead
+    track: Option<String>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "remove", desc = "Remove the result of a roll")]
+pub struct TableRemove {
+    /// Table to change
+    #[command(min_length = 1, max_length = 32)]
+    name: String,
+    /// Any die result covered by the result to remove
+    #[command(min_value = 1, max_value = 100)]
+    roll: i64,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "delete", desc = "Delete a table")]
+pub struct TableDelete {
+    /// Table to delete
+    #[command(min_length = 1, max_length = 32)]
+    name: String,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "import",
+    desc = "Add the tables of a TOML or JSON file, replacing the ones with the same names"
+)]
+pub struct TableImport {
+    /// File written like the one of /table export
+    file: Attachment,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "export", desc = "Get the tables of this server as a TOML file")]
+pub struct TableExport;
+
+#[derive(Clone, Copy, CommandOption, CreateOption)]
+pub enum RollMode {
+    #[option(name = "Add to the queue", value = "queue")]
+    Queue,
+    #[option(name = "Interrupt, the queue resumes after it", value = "interrupt")]
+    Interrupt,
+}
+
+impl SlashCommand for TableCommand {
+    // Anyone can look at the tables, rolling and editing them is up to the GM
+    const OPEN_SUBCOMMANDS: &'static [&'static str] = &["list", "show", "export"];
+
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Table command by {}", author.name);
+
+        let settings = ctx.settings.get(guild_id);
+
+        match self {
+            Self::Roll(TableRoll { name, mode }) => {
+                let name = tables::normalize(&name);
+                let table = ctx
+                    .tables
+                    .get(guild_id, &name)
+                    .ok_or(UserError::UnknownTable(name.clone()))?;
+
+                let roll = rand::thread_rng().gen_range(1..=table.die);
+                let result = table.lookup(roll).cloned();
+
+                let description = match &result {
+                    Some(result) => format!(
+                        "Rolled **{}** on a d{}\n\n*{}*",
+                        roll, table.die, result.text
+                    ),
+                    None => format!(
+                        "Rolled **{}** on a d{}, nothing on the table for it",
+                        roll, table.die
+                    ),
+                };
+                let embed_builder = EmbedBuilder::new()
+                    .title(format!("🎲 {}", name))
+                    .color(settings.embed_color)
+                    .description(description);
+
+                let Some(result) =
+                    result.filter(|result| result.tag.is_some() || result.track.is_some())
+                else {
+                    return ctx
+                        .send_embed_response(interaction, embed_builder.build())
+                        .await;
+                };
+
+                let channel_to_join = voice::channel_to_join(&ctx, guild_id, author.id)?;
+
+                ctx.defer_response(interaction).await?;
+
+                if let Some(channel_id) = channel_to_join {
+                    voice::connect(&ctx, guild_id, channel_id).await?;
+                }
+
+                // The roll is shown even when its track can't be played
+                let played = match play_result(
+                    interaction,
+                    &ctx,
+                    guild_id,
+                    &result,
+                    mode.unwrap_or(RollMode::Queue),
+                )
+                .await
+                {
+                    Ok(played) => played,
+                    Err(err) => match Error::from(err) {
+                        Error::User(err) => err.to_string(),
+                        Error::Internal(err) => {
+                            tracing::warn!("Failed to play the result of {}: {:?}", name, err);
+                            "Couldn't play it".to_owned()
+                        }
+                    },
+                };
+
+                let embed = embed_builder
+                    .field(EmbedFieldBuilder::new("Track", played).build())
+                    .build();
+                ctx.update_embed_response(interaction, embed).await
+            }
+            Self::List(_) => {
+                let tables = ctx.tables.all(guild_id);
+                if tables.is_empty() {
+                    return Err(UserError::NoResults.into());
+                }
+
+                let lines = tables
+                    .iter()
+                    .map(|(name, table)| {
+                        format!(
+                            "`{}` - d{}, {} results",
+                            name,
+                            table.die,
+                            table.results.len()
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                let embed = EmbedBuilder::new()
+                    .title("Tables")
+                    .color(settings.embed_color)
+                    .description(truncate_lines(&lines, 4096))
+                    .build();
+
+                ctx.send_embed_response(interaction, embed).await
+            }
+            Self::Show(TableShow { name }) => {
+                let name = tables::normalize(&name);
+                let table = ctx
+                    .tables
+                    .get(guild_id, &name)
+                    .ok_or(UserError::UnknownTable(name.clone()))?;
+
+                let lines = table
+                    .results
+                    .iter()
+                    .map(|result| {
+                        let mut line = format!("**{}:** {}", result.roll, result.text);
+                        if let Some(tag) = &result.tag {
+                            line.push_str(&format!(" - tag `{}`", tag));
+                        }
+                        if let Some(track) = &result.track {
+                            line.push_str(&format!(" - {}", track));
+                        }
+                        line
+                    })
+                    .collect::<Vec<_>>();
+                let description = if lines.is_empty() {
+                    "No results yet, add some with /table set".to_owned()
+                } else {
+                    truncate_lines(&lines, 4096)
+                };
+
+                let embed = EmbedBuilder::new()
+                    .title(format!("{} (d{})", name, table.die))
+                    .color(settings.embed_color)
+                    .description(description)
+                    .footer(EmbedFooterBuilder::new(format!(
+                        "{} of {} rolls covered",
+                        covered(&table),
+                        table.die
+                    )))
+                    .build();
+
+                ctx.send_embed_response(interaction, embed).await
+            }
+            Self::Create(TableCreate { name, sides }) => {
+                let name = tables::normalize(&name);
+                if !ctx.tables.create(guild_id, &name, sides as u8)? {
+                    return Err(UserError::TableExists(name).into());
+                }
+
+                ctx.send_message_response(
+                    interaction,
+                    format!(
+                        "Created `{}`, a d{} table. Add results with /table set",
+                        name, sides
+                    ),
+                )
+                .await
+            }
+            Self::Set(TableSet {
+                name,
+                roll,
+                text,
+                tag,
+                track,
+            }) => {
+                let name = tables::normalize(&name);
+                let roll = roll.parse::<Roll>().map_err(UserError::InvalidTable)?;
+                let result = TableResult {
+                    roll,
+                    text,
+                    tag: tag.map(|tag| tags::normalize(&tag)),
+                    track,
+                };
+
+                // Checked on a copy, so an invalid result leaves the table as it was
+                let mut table = ctx
+                    .tables
+                    .get(guild_id, &name)
+                    .ok_or(UserError::UnknownTable(name.clone()))?;
+                table.set(result.clone());
+                table.validate().map_err(UserError::InvalidTable)?;
+
+                ctx.tables
+                    .update(guild_id, &name, |table| table.set(result))?
+                    .ok_or(UserError::UnknownTable(name.clone()))?;
+
+                ctx.send_message_response(interaction, format!("Set {} of `{}`", roll, name))
+                    .await
+            }
+            Self::Remove(TableRemove { name, roll }) => {
+                let name = tables::normalize(&name);
+                let removed = ctx
+                    .tables
+                    .update(guild_id, &name, |table| table.remove(roll as u8))?
+                    .ok_or(UserError::UnknownTable(name.clone()))?
+                    .ok_or(UserError::NoResults)?;
+
+                ctx.send_message_response(
+                    interaction,
+                    format!("Removed {} of `{}`", removed.roll, name),
+                )
+                .await
+            }
+            Self::Delete(TableDelete { name }) => {
+                let name = tables::normalize(&name);
+                if !ctx.tables.delete(guild_id, &name)? {
+                    return Err(UserError::UnknownTable(name).into());
+                }
+
+                ctx.send_message_response(interaction, format!("Deleted `{}`", name))
+                    .await
+            }
+            Self::Import(TableImport { file }) => {
+                if file.size > MAX_IMPORT_SIZE {
+                    return Err(UserError::InvalidTable(format!(
+                        "the file is over {} KiB",
+                        MAX_IMPORT_SIZE / 1024
+                    ))
+                    .into());
+                }
+
+                ctx.defer_response(interaction).await?;
+
+                let content = download(&ctx, &file.url).await?;
+                let imported = parse_tables(&file.filename, &content)?;
+                let names = imported
+                    .keys()
+                    .map(|name| format!("`{}`", name))
+                    .collect::<Vec<_>>();
+                ctx.tables.import(guild_id, imported)?;
+
+                ctx.update_message_response(
+                    interaction,
+                    format!("Imported {} tables: {}", names.len(), names.join(", ")),
+                )
+                .await
+            }
+            Self::Export(_) => {
+                let tables = ctx.tables.all(guild_id);
+                if tables.is_empty() {
+                    return Err(UserError::NoResults.into());
+                }
+
+                let data = InteractionResponseDataBuilder::new().content(format!(
+                    "{} tables, edit the file and bring it back with /table import",
+                    tables.len()
+                ));
+                ctx.send_file_response(interaction, data, "tables.toml", toml::to_string(&tables)?)
+                    .await
+            }
+        }
+    }
+}
+
+/// Number of die results that have a result
+fn covered(table: &Table) -> usize {
+    (1..=table.die)
+        .filter(|roll| table.lookup(*roll).is_some())
+        .count()
+}
+
+/// Queue the track of the result, or play it right away. Returns what happened for the embed
+async fn play_result(
+    interaction: &Interaction,
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    result: &TableResult,
+    mode: RollMode,
+) -> anyhow::Result<String> {
+    let channel_id = interaction
+        .channel
+        .as_ref()
+        .map(|channel| channel.id)
+        .ok_or(anyhow::anyhow!("Invalid channel id"))?;
+    let requester = interaction
+        .author_id()
+        .ok_or(anyhow::anyhow!("No author found"))?;
+
+    let track = result_track(ctx, guild_id, result).await?;
+    let description = format!(
+        "[{}]({})",
+        track.info.title.clone().unwrap_or("<Unknown>".to_string()),
+        track.info.uri
+    );
+
+    match mode {
+        RollMode::Queue => {
+            ctx.enqueue(guild_id, vec![track], channel_id, requester)
+                .await?
+                .into_iter()
+                .collect::<Result<(), _>>()?;
+
+            Ok(format!("Queued {}", description))
+        }
+        RollMode::Interrupt => {
+            scene::switch_scene(
+                ctx,
+                guild_id,
+                channel_id,
+                requester,
+                vec![track],
+                SceneMode::Interrupt,
+                SceneEffects::default(),
+            )
+            .await?;
+
+            Ok(format!("Playing {}", description))
+        }
+    }
+}
+
+async fn result_track(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    result: &TableResult,
+) -> anyhow::Result<TwilightTrack> {
+    if let Some(tag) = &result.tag {
+        let tag = tags::normalize(tag);
+        return ctx
+            .tags
+            .pool(guild_id, &tag)
+            .choose(&mut rand::thread_rng())
+            .cloned()
+            .ok_or(UserError::UnknownTag(tag).into());
+    }
+
+    let query = result
+        .track
+        .as_deref()
+        .ok_or(anyhow::anyhow!("Table result without tag nor track"))?;
+    let loaded = load_tracks(ctx, guild_id, search_query(query)).await?;
+
+    match loaded.load_type {
+        LoadType::LoadFailed => Err(UserError::LoadFailed.into()),
+        LoadType::NoMatches => Err(UserError::NoResults.into()),
+        _ => loaded
+            .tracks
+            .into_iter()
+            .next()
+            .ok_or(UserError::NoResults.into()),
+    }
+}
+
+async fn download(ctx: &Context, url: &str) -> anyhow::Result<String> {
+    let response = ctx
+        .hyper_client
+        .request(Request::get(url).body(Body::empty())?)
+        .await?;
+    if !response.status().is_success() {
+        anyhow::bail!("Failed to download {}: {}", url, response.status());
+    }
+    let bytes = hyper::body::to_bytes(response.into_body()).await?;
+
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| UserError::InvalidTable("the file is not text".to_owned()).into())
+}
+
+/// Tables of an imported file, JSON when its name says so and TOML otherwise
+fn parse_tables(filename: &str, content: &str) -> Result<BTreeMap<String, Table>, UserError> {
+    let parsed = if filename.to_lowercase().ends_with(".json") {
+        serde_json::from_str::<BTreeMap<String, Table>>(content).map_err(|err| err.to_string())
+    } else {
+        toml::from_str::<BTreeMap<String, Table>>(content).map_err(|err| err.message().to_owned())
+    };
+    let parsed = parsed.map_err(UserError::InvalidTable)?;
+    if parsed.is_empty() {
+        return Err(UserError::InvalidTable("the file has no tables".to_owned()));
+    }
+
+    let mut tables = BTreeMap::new();
+    for (name, mut table) in parsed {
+        let name = tables::normalize(&name);
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(UserError::InvalidTable(format!(
+                "`{}` is not a name, use at most {} characters",
+                name, MAX_NAME_LEN
+            )));
+        }
+        for result in &mut table.results {
+            result.tag = result.tag.as_deref().map(tags::normalize);
+        }
+        table
+            .validate()
+            .map_err(|reason| UserError::InvalidTable(format!("{}: {}", name, reason)))?;
+        // Names that only differ by case or spaces would silently replace each other
+        if tables.contains_key(&name) {
+            return Err(UserError::InvalidTable(format!(
+                "`{}` is in the file more than once",
+                name
+            )));
+        }
+        tables.insert(name, table);
+    }
+
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::TableResult;
+
+    #[test]
+    fn exported_tables_import_back() {
+        let mut table = Table::new(20);
+        table.set(TableResult {
+            roll: "1-10".parse().unwrap(),
+            text: "An ambush".to_owned(),
+            tag: Some("combat".to_owned()),
+            track: None,
+        });
+        table.set(TableResult {
+            roll: "11".parse().unwrap(),
+            text: "A quiet night".to_owned(),
+            tag: None,
+            track: Some("https://example.com/night.mp3".to_owned()),
+        });
+        let tables = BTreeMap::from([("encounters".to_owned(), table)]);
+
+        let exported = toml::to_string(&tables).unwrap();
+        assert_eq!(parse_tables("tables.toml", &exported).unwrap(), tables);
+
+        let exported = serde_json::to_string(&tables).unwrap();
+        assert_eq!(parse_tables("tables.json", &exported).unwrap(), tables);
+    }
+
+    #[test]
+    fn imported_names_and_tags_are_normalized() {
+        let imported = parse_tables(
+            "tables.toml",
+            r#"
+            [Encounters]
+            die = 6
+
+            [[Encounters.result]]
+            roll = "1-6"
+            text = "A fight"
+            tag = " Combat "
+            "#,
+        )
+        .unwrap();
+
+        let table = &imported["encounters"];
+        assert_eq!(table.results[0].tag.as_deref(), Some("combat"));
+    }
+
+    #[test]
+    fn invalid_imports_are_refused() {
+        assert!(parse_tables("tables.toml", "").is_err());
+        assert!(parse_tables("tables.json", "{").is_err());
+        assert!(parse_tables(
+            "tables.toml",
+            r#"
+            [encounters]
+            die = 6
+
+            [[encounters.result]]
+            roll = "5-8"
+            text = "Off the die"
+            "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn names_equal_once_normalized_are_refused() {
+        let imported = parse_tables(
+            "tables.json",
+            r#"{
+                "Encounters": { "die": 6, "result": [{ "roll": "1-6", "text": "A fight" }] },
+                "encounters": { "die": 4, "result": [{ "roll": "1-4", "text": "A chase" }] }
+            }"#,
+        );
+        assert!(matches!(
+            imported,
+            Err(UserError::InvalidTable(reason)) if reason.contains("more than once")
+        ));
+    }
+}
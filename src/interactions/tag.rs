@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_lavalink::http::Track as TwilightTrack;
+use twilight_model::{
+    application::interaction::Interaction,
+    id::{
+        marker::{GuildMarker, UserMarker},
+        Id,
+    },
+};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFooterBuilder};
+
+use super::SlashCommand;
+use crate::{
+    context::Context,
+    error::UserError,
+    tags,
+    utils::{from_ms_to_minutes, truncate_lines},
+};
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "tag",
+    desc = "Sort tracks by mood, for /scene",
+    dm_permission = false
+)]
+pub enum TagCommand {
+    #[command(name = "add")]
+    Add(TagAdd),
+    #[command(name = "remove")]
+    Remove(TagRemove),
+    #[command(name = "list")]
+    List(TagList),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(
+    name = "add",
+    desc = "Tag the current track, or one from the history or your favorites"
+)]
+pub struct TagAdd {
+    /// Mood of the track, like combat or bar
+    #[command(min_length = 1, max_length = 32)]
+    tag: String,
+    /// Number of the entry in /history instead of the current track
+    #[command(min_value = 1)]
+    history: Option<i64>,
+    /// Number of the track in /favorites list instead of the current one
+    #[command(min_value = 1)]
+    favorite: Option<i64>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "remove", desc = "Remove a track from a tag")]
+pub struct TagRemove {
+    /// Tag to remove the track from
+    #[command(min_length = 1, max_length = 32)]
+    tag: String,
+    /// Number of the track in /tag list
+    #[command(min_value = 1)]
+    position: i64,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "list", desc = "Show the tags, or the tracks of one")]
+pub struct TagList {
+    /// Tag to show the tracks of
+    #[command(min_length = 1, max_length = 32)]
+    tag: Option<String>,
+}
+
+impl SlashCommand for TagCommand {
+    // The pools decide what scenes play, only listing them stays open when tag is DJ-only
+    const OPEN_SUBCOMMANDS: &'static [&'static str] = &["list"];
+
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Tag command by {}", author.name);
+
+        match self {
+            Self::Add(TagAdd {
+                tag,
+                history,
+                favorite,
+            }) => {
+                let tag = tags::normalize(&tag);
+                let track = source_track(&ctx, guild_id, author.id, history, favorite)?;
+                let title = track.info.title.clone().unwrap_or("<Unknown>".to_string());
+
+                let content = if ctx.tags.add(guild_id, &tag, track)? {
+                    format!("Tagged **{}** as `{}`", title, tag)
+                } else {
+                    format!("**{}** is already tagged `{}`", title, tag)
+                };
+
+                ctx.send_message_response(interaction, content).await
+            }
+            Self::Remove(TagRemove { tag, position }) => {
+                let tag = tags::normalize(&tag);
+                let pool = ctx.tags.pool(guild_id, &tag);
+                if pool.is_empty() {
+                    return Err(UserError::UnknownTag(tag).into());
+                }
+
+                let removed = ctx
+                    .tags
+                    .remove(guild_id, &tag, position as usize - 1)?
+                    .ok_or(UserError::NoSuchTagged {
+                        tag: tag.clone(),
+                        max: pool.len(),
+                    })?;
+
+                let content = format!(
+                    "Removed **{}** from `{}`",
+                    removed.info.title.unwrap_or("<Unknown>".to_string()),
+                    tag
+                );
+                ctx.send_message_response(interaction, content).await
+            }
+            Self::List(TagList { tag: None }) => {
+                let tags = ctx.tags.tags(guild_id);
+                if tags.is_empty() {
+                    return Err(UserError::NoResults.into());
+                }
+
+                let lines = tags
+                    .iter()
+                    .map(|(tag, count)| format!("`{}` - {} tracks", tag, count))
+                    .collect::<Vec<_>>();
+
+                let embed = EmbedBuilder::new()
+                    .title("Tags")
+                    .color(ctx.settings.get(guild_id).embed_color)
+                    .description(truncate_lines(&lines, 4096))
+                    .build();
+
+                ctx.send_embed_response(interaction, embed).await
+            }
+            Self::List(TagList { tag: Some(tag) }) => {
+                let tag = tags::normalize(&tag);
+                let pool = ctx.tags.pool(guild_id, &tag);
+                if pool.is_empty() {
+                    return Err(UserError::UnknownTag(tag).into());
+                }
+
+                let lines = pool
+                    .iter()
+                    .enumerate()
+                    .map(|(i, track)| {
+                        format!(
+                            "**{}:** [{}]({}) - {}",
+                            i + 1,
+                            track.info.title.clone().unwrap_or("<Unknown>".to_string()),
+                            track.info.uri,
+                            from_ms_to_minutes(track.info.length)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                let embed = EmbedBuilder::new()
+                    .title(format!("Tracks tagged `{}`", tag))
+                    .color(ctx.settings.get(guild_id).embed_color)
+                    .description(truncate_lines(&lines, 4096))
+                    .footer(EmbedFooterBuilder::new(format!("{} tracks", pool.len())))
+                    .build();
+
+                ctx.send_embed_response(interaction, embed).await
+            }
+        }
+    }
+}
+
+/// Track picked by the options of /tag add, the current one when none is given
+fn source_track(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    history: Option<i64>,
+    favorite: Option<i64>,
+) -> anyhow::Result<TwilightTrack> {
+    match (history, favorite) {
+        (Some(_), Some(_)) => Err(UserError::InvalidOptions(
+            "pick either a history entry or a favorite".to_owned(),
+        )
+        .into()),
+        (Some(entry_id), None) => ctx
+            .history
+            .get(guild_id, entry_id)?
+            .map(|entry| entry.track)
+            .ok_or(UserError::NoHistoryEntry.into()),
+        (None, Some(position)) => {
+            let favorites = ctx.favorites.get(user_id);
+            if favorites.is_empty() {
+                return Err(UserError::NoFavorites.into());
+            }
+
+            let max = favorites.len();
+            favorites
+                .into_iter()
+                .nth(position as usize - 1)
+                .ok_or(UserError::NoSuchFavorite { max }.into())
+        }
+        (None, None) => ctx
+            .get_queue(guild_id)
+            .and_then(|queue| queue.lock().unwrap().peek().ok())
+            .map(|track| track.inner().clone())
+            .ok_or(UserError::EmptyQueue.into()),
+    }
+}
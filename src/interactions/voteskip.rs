@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use twilight_gateway::ShardId;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_lavalink::model::Stop;
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::message::component::{Button, ButtonStyle, Component},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    id::{
+        marker::{GuildMarker, UserMarker},
+        Id,
+    },
+};
+use twilight_util::builder::InteractionResponseDataBuilder;
+
+use super::{now_playing, precondition::Precondition, SlashCommand};
+use crate::{context::Context, error::UserError};
+
+/// Custom id of the button on the now playing message, followed by the history entry id
+pub const BUTTON_ID: &str = "voteskip";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "voteskip", desc = "Vote to skip the current track")]
+pub struct VoteSkipCommand;
+
+impl SlashCommand for VoteSkipCommand {
+    const PRECONDITIONS: &'static [Precondition] = &[
+        Precondition::BotInVoice,
+        Precondition::UserInBotChannel,
+        Precondition::QueueNotEmpty,
+    ];
+
+    async fn run(
+        self,
+        interaction: &Interaction,
+        ctx: Arc<Context>,
+        _shard_id: ShardId,
+    ) -> anyhow::Result<()> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+        let author = interaction
+            .author()
+            .ok_or(anyhow::anyhow!("No author found"))?;
+
+        tracing::debug!("Voteskip command by {}", author.name);
+
+        // Read before voting, a passed vote starts the next track
+        let entry_id = ctx.history.playing(guild_id);
+        let vote = vote(&ctx, guild_id, author.id).await?;
+        if let Some(entry_id) = entry_id {
+            update_now_playing(&ctx, guild_id, vote_buttons(entry_id, &vote)).await;
+        }
+
+        let content = match vote {
+            Vote::Passed => "Vote passed, skipped current track".to_owned(),
+            Vote::Pending { votes, needed } => {
+                format!("Voted to skip the current track ({}/{})", votes, needed)
+            }
+        };
+
+        ctx.send_message_response(interaction, content).await
+    }
+}
+
+/// Vote button pressed on a now playing message, its label is updated with the progress
+pub async fn press(
+    interaction: &Interaction,
+    entry_id: &str,
+    ctx: Arc<Context>,
+    _shard_id: ShardId,
+) -> anyhow::Result<()> {
+    let guild_id = interaction
+        .guild_id
+        .ok_or(anyhow::anyhow!("Invalid guild id"))?;
+
+    let author = interaction
+        .author()
+        .ok_or(anyhow::anyhow!("No author found"))?;
+
+    tracing::debug!("Voteskip button by {}", author.name);
+
+    // Buttons of older now playing messages stay around after their track ended
+    let entry_id = entry_id
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid history entry id {}", entry_id))?;
+    if ctx.history.playing(guild_id) != Some(entry_id) {
+        return Err(UserError::VoteExpired.into());
+    }
+
+    let vote = vote(&ctx, guild_id, author.id).await?;
+    let buttons = vote_buttons(entry_id, &vote);
+
+    let response = InteractionResponse {
+        kind: InteractionResponseType::UpdateMessage,
+        data: Some(
+            InteractionResponseDataBuilder::new()
+                .components([buttons])
+                .build(),
+        ),
+    };
+
+    ctx.interaction_client()
+        .await?
+        .create_response(interaction.id, &interaction.token, &response)
+        .await?;
+
+    Ok(())
+}
+
+/// Vote button of the now playing message, its label shows the progress of the vote
+pub fn button(entry_id: i64, label: impl Into<String>, disabled: bool) -> Component {
+    Component::Button(Button {
+        custom_id: Some(format!("{}:{}", BUTTON_ID, entry_id)),
+        disabled,
+        emoji: None,
+        label: Some(label.into()),
+        style: ButtonStyle::Secondary,
+        url: None,
+    })
+}
+
+/// Buttons of the now playing message with the progress of the vote
+fn vote_buttons(entry_id: i64, vote: &Vote) -> Component {
+    match vote {
+        Vote::Passed => now_playing::buttons(entry_id, "Skipped", true),
+        Vote::Pending { votes, needed } => {
+            now_playing::buttons(entry_id, format!("Vote skip ({}/{})", votes, needed), false)
+        }
+    }
+}
+
+/// Edit the buttons of the guild's now playing message, a failure only leaves a stale label
+async fn update_now_playing(ctx: &Context, guild_id: Id<GuildMarker>, buttons: Component) {
+    let Some((channel_id, message_id)) = ctx.now_playing_messages.get(&guild_id).map(|ids| *ids)
+    else {
+        return;
+    };
+
+    let components = [buttons];
+    let update = ctx
+        .http_client
+        .update_message(channel_id, message_id)
+        .components(Some(&components));
+    let result = match update {
+        Ok(update) => update.await.map(|_| ()).map_err(anyhow::Error::from),
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = result {
+        tracing::warn!(
+            "Failed to update the now playing message in {}: {:?}",
+            guild_id,
+            err
+        );
+    }
+}
+
+enum Vote {
+    Passed,
+    Pending { votes: usize, needed: usize },
+}
+
+/// Count the vote of the user, skipping the track once enough listeners voted
+async fn vote(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+) -> anyhow::Result<Vote> {
+    let channel_id = ctx
+        .bot_voice_channel(guild_id)
+        .ok_or(UserError::BotNotInVoice)?;
+    let listeners = ctx.humans_in_channel(channel_id);
+    if !listeners.contains(&user_id) {
+        return Err(UserError::UserNotInBotChannel.into());
+    }
+
+    let percent = ctx.settings.get(guild_id).vote_skip_percent as usize;
+    let needed = (listeners.len() * percent).div_ceil(100).max(1);
+
+    let votes = {
+        let mut votes = ctx.skip_votes.entry(guild_id).or_default();
+        // Votes of users who left the channel don't count anymore
+        votes.retain(|voter| listeners.contains(voter));
+        votes.insert(user_id);
+        votes.len()
+    };
+
+    if votes < needed {
+        return Ok(Vote::Pending { votes, needed });
+    }
+
+    ctx.skip_votes.remove(&guild_id);
+    let player = ctx.lavalink.player(guild_id).await?;
+    player.send(Stop::from(guild_id))?;
+
+    Ok(Vote::Passed)
+}
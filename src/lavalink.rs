@@ -1,113 +1,257 @@
 use std::sync::Arc;
 
 use futures::StreamExt;
+use hyper::{Body, Request};
 use twilight_lavalink::{
+    http::LoadedTracks,
     model::{IncomingEvent, Play, Stop},
     node::IncomingEvents,
 };
-use twilight_model::id::{marker::ChannelMarker, Id};
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker},
+    Id,
+};
 use twilight_util::builder::embed::EmbedBuilder;
 
-use crate::{context::Context, queue::QueueLoopMode};
+use crate::{
+    context::Context, history::EndReason, inactivity, interactions::now_playing,
+    queue::QueueLoopMode, voice,
+};
+
+/// Number of load requests sent to lavalink at the same time
+/// by the commands that load many tracks at once
+pub const MAX_CONCURRENT_LOADS: usize = 4;
+
+/// Links are loaded as they are, anything else is searched on youtube
+pub fn search_query(input: &str) -> String {
+    if input.starts_with("http") {
+        input.to_string()
+    } else {
+        format!("ytsearch:{}", input)
+    }
+}
+
+/// Resolve a link or a prefixed search query (e.g. `ytsearch:`)
+/// through the node of the guild's player
+pub async fn load_tracks(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    query: impl Into<String>,
+) -> anyhow::Result<LoadedTracks> {
+    let player = ctx.lavalink.player(guild_id).await?;
+
+    let (parts, body) = twilight_lavalink::http::load_track(
+        player.node().config().address,
+        query.into(),
+        &player.node().config().authorization,
+    )?
+    .into_parts();
+
+    let req = Request::from_parts(parts, Body::from(body));
+    let res = ctx.hyper_client.request(req).await?;
+    let res_bytes = hyper::body::to_bytes(res.into_body()).await?;
+
+    Ok(serde_json::from_slice::<LoadedTracks>(&res_bytes)?)
+}
 
 pub async fn handle_events(mut events: IncomingEvents, ctx: Arc<Context>) -> anyhow::Result<()> {
-    
     while let Some(event) = events.next().await {
-        match event {
-            IncomingEvent::TrackEnd(e) => {
-                tracing::debug!("Track end");
-                let player = ctx.lavalink.player(e.guild_id).await?;
-                let mut channel_id: Option<Id<ChannelMarker>> = None;
-                let mut end_of_queue = false;
-                {
-                    let queue_arc = ctx.get_queue(e.guild_id).ok_or(anyhow::anyhow!(
-                        "No queue found for guild id {}",
-                        e.guild_id
-                    ))?;
-                    let queue = queue_arc.lock().unwrap();
-
-                    let next_track = match queue.loop_mode {
-                        QueueLoopMode::None => {
-                            if queue.is_empty() {
-                                end_of_queue = true;
-                                None
-                            } else if queue.len() == 1 {
+        // One guild's failure can't stop the events of every other guild on the node
+        if let Err(err) = handle_event(&ctx, event).await {
+            tracing::error!("Failed to handle a lavalink event: {:?}", err);
+        }
+    }
+    Ok(())
+}
+
+async fn handle_event(ctx: &Arc<Context>, event: IncomingEvent) -> anyhow::Result<()> {
+    match event {
+        IncomingEvent::TrackEnd(e) => {
+            tracing::debug!("Track end");
+            if let Err(err) = ctx
+                .history
+                .record_end(e.guild_id, EndReason::from_lavalink(&e.reason))
+            {
+                tracing::warn!(
+                    "Failed to record the end of a track in {}: {:?}",
+                    e.guild_id,
+                    err
+                );
+            }
+            // The player was destroyed, the queue is cleared or kept for the next connection
+            if e.reason == "CLEANUP" {
+                return Ok(());
+            }
+            // Another track was started over this one (a scene), the queue is already in place
+            if e.reason == "REPLACED" {
+                return Ok(());
+            }
+            let player = ctx.lavalink.player(e.guild_id).await?;
+            let mut channel_id: Option<Id<ChannelMarker>> = None;
+            let mut end_of_queue = false;
+            {
+                let queue_arc = ctx.get_queue(e.guild_id).ok_or(anyhow::anyhow!(
+                    "No queue found for guild id {}",
+                    e.guild_id
+                ))?;
+                let queue = queue_arc.lock().unwrap();
+
+                let next_track = match queue.loop_mode {
+                    QueueLoopMode::None => {
+                        if queue.is_empty() {
+                            end_of_queue = true;
+                            None
+                        } else if queue.len() == 1 {
                             // Last track in queue played
-                                channel_id = Some(queue.peek()?.channel_id);
-                                player.send(Stop::from(e.guild_id))?;
-                                queue.pop()?;
-                                end_of_queue = true;
-                                None
-                            } else {
-                                queue.pop()?;
-                                Some(queue.peek()?)
-                            }
-                        }
-                        QueueLoopMode::LoopQueue => {
-                            let current_track = queue.peek()?;
-                            queue.push(current_track);
+                            channel_id = Some(queue.peek()?.channel_id);
+                            player.send(Stop::from(e.guild_id))?;
+                            queue.pop()?;
+                            end_of_queue = true;
+                            None
+                        } else {
                             queue.pop()?;
-
                             Some(queue.peek()?)
                         }
-                        QueueLoopMode::LoopTrack => Some(queue.peek()?),
-                    };
-
-                    if let Some(track) = next_track {
-                        player.send(Play::from((e.guild_id, track.track())))?;
                     }
-                }
+                    QueueLoopMode::LoopQueue => {
+                        // Played in full on the next round
+                        let mut current_track = queue.peek()?;
+                        current_track.start_time = None;
+                        queue.push(current_track);
+                        queue.pop()?;
 
-                if end_of_queue {
-                    tracing::debug!("End of queue");
-                    if let Some(id) = channel_id {
-                        ctx.http_client
-                            .create_message(id)
-                            .embeds(&vec![EmbedBuilder::new()
-                                .color(0xe04f2e)
-                                .title("End of queue")
-                                .build()])?
-                            .await?;
+                        Some(queue.peek()?)
                     }
+                    QueueLoopMode::LoopTrack => Some(queue.peek()?),
+                };
+
+                if let Some(track) = next_track {
+                    // A looped track starts over, a track cut by a scene resumes
+                    let start_time = match queue.loop_mode {
+                        QueueLoopMode::LoopTrack => None,
+                        _ => track.start_time,
+                    };
+                    player.send(Play::from((e.guild_id, track.track(), start_time)))?;
                 }
             }
-            IncomingEvent::TrackStart(start) => {
-                tracing::debug!("Track start");
-                let mut embed_builder = EmbedBuilder::new().color(0xe04f2e);
-                let channel_id: Id<ChannelMarker>;
-                {
-                    let queue_arc = ctx.get_queue(start.guild_id).ok_or(anyhow::anyhow!(
-                        "No queue found for guild id {}",
-                        start.guild_id
-                    ))?;
-                    let queue = queue_arc.lock().unwrap();
-
-                    let track = queue.peek()?;
-                    channel_id = track.channel_id;
-
-                    let title = track
-                        .info()
-                        .title
-                        .clone()
-                        .unwrap_or("<Unknown>".to_string());
-                    let uri = &track.info().uri;
-                    let author = track
-                        .info()
-                        .author
-                        .clone()
-                        .unwrap_or("<Unknown>".to_string());
-                    embed_builder = embed_builder
-                        .title("Now playing".to_owned())
-                        .description(format!("**[{}]({})** \n By **{}**", title, uri, author));
+
+            if end_of_queue {
+                tracing::debug!("End of queue");
+                inactivity::mark_idle(ctx, e.guild_id);
+                let settings = ctx.settings.get(e.guild_id);
+                if let Some(id) = settings.announce_channel.or(channel_id) {
+                    ctx.http_client
+                        .create_message(id)
+                        .embeds(&[EmbedBuilder::new()
+                            .color(settings.embed_color)
+                            .title("End of queue")
+                            .build()])?
+                        .await?;
                 }
+            }
+        }
+        IncomingEvent::TrackStart(start) => {
+            tracing::debug!("Track start");
+            // Votes were for the previous track
+            ctx.skip_votes.remove(&start.guild_id);
+            ctx.now_playing_messages.remove(&start.guild_id);
+            inactivity::mark_active(ctx, start.guild_id);
+
+            let current = ctx
+                .get_queue(start.guild_id)
+                .and_then(|queue| queue.lock().unwrap().peek().ok());
+            let entry_id = current.and_then(|track| {
+                ctx.history
+                    .record_start(start.guild_id, &track)
+                    .map_err(|err| {
+                        tracing::warn!(
+                            "Failed to record a track start in {}: {:?}",
+                            start.guild_id,
+                            err
+                        )
+                    })
+                    .ok()
+            });
+
+            if let Err(err) = voice::update_stage_topic(ctx, start.guild_id).await {
+                tracing::warn!(
+                    "Failed to set the stage topic in {}: {:?}",
+                    start.guild_id,
+                    err
+                );
+            }
+
+            let settings = ctx.settings.get(start.guild_id);
+            if !settings.announce_now_playing {
+                return Ok(());
+            }
 
-                if let Err(err) = ctx.http_client.create_message(channel_id).embeds(&vec![embed_builder.build()])?.await {
-                    tracing::error!("{}", err);
-                    tracing::debug!("{:?}", err.kind());
+            let mut embed_builder = EmbedBuilder::new().color(settings.embed_color);
+            let channel_id: Id<ChannelMarker>;
+            let mut components = Vec::new();
+            {
+                let queue_arc = ctx.get_queue(start.guild_id).ok_or(anyhow::anyhow!(
+                    "No queue found for guild id {}",
+                    start.guild_id
+                ))?;
+                let queue = queue_arc.lock().unwrap();
+
+                let track = queue.peek()?;
+                channel_id = settings.announce_channel.unwrap_or(track.channel_id);
+                // Without a history entry the buttons would have nothing to refer to
+                if let Some(entry_id) = entry_id {
+                    components.push(now_playing::buttons(entry_id, "Vote skip", false));
                 }
+
+                let title = track
+                    .info()
+                    .title
+                    .clone()
+                    .unwrap_or("<Unknown>".to_string());
+                let uri = &track.info().uri;
+                let author = track
+                    .info()
+                    .author
+                    .clone()
+                    .unwrap_or("<Unknown>".to_string());
+                embed_builder = embed_builder.title("Now playing".to_owned());
+                // Discord refuses anything but web links, like the path of a library track
+                let title = if uri.starts_with("http") {
+                    embed_builder = embed_builder.url(uri);
+                    format!("[{}]({})", title, uri)
+                } else {
+                    title
+                };
+                embed_builder =
+                    embed_builder.description(format!("**{}** \n By **{}**", title, author));
+            }
+
+            // A message discord would refuse is logged like a failed request
+            let embeds = [embed_builder.build()];
+            let message = ctx
+                .http_client
+                .create_message(channel_id)
+                .embeds(&embeds)
+                .and_then(|message| message.components(&components));
+            match message {
+                Ok(message) => match message.await {
+                    Ok(response) => {
+                        if entry_id.is_some() {
+                            let message = response.model().await?;
+                            ctx.now_playing_messages
+                                .insert(start.guild_id, (channel_id, message.id));
+                        }
+                    }
+                    Err(err) => {
+                        tracing::error!("{}", err);
+                        tracing::debug!("{:?}", err.kind());
+                    }
+                },
+                Err(err) => tracing::error!("Invalid now playing message: {}", err),
             }
-            _ => {}
         }
+        _ => {}
     }
+
     Ok(())
 }
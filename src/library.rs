@@ -0,0 +1,195 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use lofty::prelude::{Accessor, AudioFile, TaggedFileExt};
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use walkdir::WalkDir;
+
+use crate::context::Context;
+
+// Files lavalink's local source can play
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "ogg", "opus", "wav", "m4a", "aac"];
+
+// Changes usually come in bursts (copying a folder), the rescan waits for them to settle
+const RESCAN_DELAY: Duration = Duration::from_secs(5);
+
+/// An audio file of the library, with what its tags say about it
+#[derive(Debug, Clone)]
+pub struct LibraryTrack {
+    pub path: PathBuf,
+    /// From the tags, or the file name without tags
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub duration: Duration,
+}
+
+impl LibraryTrack {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let file = lofty::read_from_path(path)?;
+        let tag = file.primary_tag().or(file.first_tag());
+
+        let title = tag
+            .and_then(|tag| tag.title())
+            .map(|title| title.into_owned())
+            .or(path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        Ok(Self {
+            path: path.to_owned(),
+            title,
+            artist: tag
+                .and_then(|tag| tag.artist())
+                .map(|artist| artist.into_owned()),
+            album: tag
+                .and_then(|tag| tag.album())
+                .map(|album| album.into_owned()),
+            genre: tag
+                .and_then(|tag| tag.genre())
+                .map(|genre| genre.into_owned()),
+            duration: file.properties().duration(),
+        })
+    }
+
+    fn matches(&self, words: &[String], genre: Option<&str>) -> bool {
+        let genre_matches = genre.is_none_or(|genre| {
+            self.genre
+                .as_ref()
+                .is_some_and(|own| own.to_lowercase().contains(&genre.to_lowercase()))
+        });
+
+        let text = [
+            Some(&self.title),
+            self.artist.as_ref(),
+            self.album.as_ref(),
+            self.genre.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|field| field.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+        genre_matches && words.iter().all(|word| text.contains(word))
+    }
+}
+
+/// Catalog of the configured music directory, kept in memory and rebuilt when files change
+pub struct Library {
+    root: Option<PathBuf>,
+    tracks: RwLock<Vec<LibraryTrack>>,
+}
+
+impl Library {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self {
+            root,
+            tracks: RwLock::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.root.is_some()
+    }
+
+    /// Read the tags of every audio file under the directory, returns the number of tracks found.
+    /// Blocking, it reads every file
+    pub fn scan(&self) -> usize {
+        let Some(root) = &self.root else {
+            return 0;
+        };
+
+        // Lavalink would resolve relative paths from its own working directory
+        let root = root.canonicalize().unwrap_or(root.clone());
+
+        let mut tracks = WalkDir::new(root)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && is_audio_file(entry.path()))
+            .filter_map(|entry| match LibraryTrack::read(entry.path()) {
+                Ok(track) => Some(track),
+                Err(err) => {
+                    tracing::warn!("Skipping {}: {}", entry.path().display(), err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        tracks.sort_by_key(|track| track.title.to_lowercase());
+
+        let count = tracks.len();
+        *self.tracks.write().unwrap() = tracks;
+
+        count
+    }
+
+    /// Tracks whose title, artist, album or genre contain every word of the query
+    pub fn search(&self, query: &str, genre: Option<&str>) -> Vec<LibraryTrack> {
+        let words = query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+
+        self.tracks
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|track| track.matches(&words, genre))
+            .cloned()
+            .collect()
+    }
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Scan the library, then rescan it whenever something changes in the directory
+pub async fn watch(ctx: Arc<Context>) {
+    if let Err(err) = watch_changes(ctx).await {
+        tracing::error!("Stopped watching the music library: {:?}", err);
+    }
+}
+
+async fn watch_changes(ctx: Arc<Context>) -> anyhow::Result<()> {
+    let Some(root) = ctx.config.library.path.clone() else {
+        return Ok(());
+    };
+
+    // The watcher calls back from its own thread
+    let (sender, mut changes) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok_and(|event| !event.kind.is_access()) {
+            let _ = sender.send(());
+        }
+    })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+
+    rescan(&ctx).await?;
+
+    while changes.recv().await.is_some() {
+        tokio::time::sleep(RESCAN_DELAY).await;
+        while changes.try_recv().is_ok() {}
+
+        rescan(&ctx).await?;
+    }
+
+    Ok(())
+}
+
+async fn rescan(ctx: &Arc<Context>) -> anyhow::Result<()> {
+    let ctx = ctx.clone();
+    let count = tokio::task::spawn_blocking(move || ctx.library.scan()).await?;
+    tracing::info!("Music library scanned, {} tracks", count);
+
+    Ok(())
+}
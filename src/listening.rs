@@ -0,0 +1,47 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::context::Context;
+
+// How often the listeners of every playing guild are counted
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Credit the users in the bot's voice channel with the time it spent playing to them.
+/// Sampling the voice states is coarse but doesn't need to follow every join and leave
+pub async fn sample_listeners(ctx: Arc<Context>) {
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    // The first tick completes right away, there is nothing to count yet
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let playing_guilds = ctx
+            .queues
+            .iter()
+            .filter(|entry| !entry.value().lock().unwrap().is_empty())
+            .map(|entry| *entry.key())
+            .collect::<Vec<_>>();
+
+        for guild_id in playing_guilds {
+            let paused = ctx
+                .lavalink
+                .players()
+                .get(&guild_id)
+                .is_none_or(|player| player.paused());
+            let Some(channel_id) = ctx.bot_voice_channel(guild_id) else {
+                continue;
+            };
+            if paused {
+                continue;
+            }
+
+            let listeners = ctx.humans_in_channel(channel_id);
+            if let Err(err) =
+                ctx.history
+                    .add_listening(guild_id, &listeners, SAMPLE_INTERVAL.as_secs())
+            {
+                tracing::warn!("Failed to count the listeners of {}: {:?}", guild_id, err);
+            }
+        }
+    }
+}
@@ -1,55 +1,92 @@
-use std::{net::SocketAddr, str::FromStr, sync::Arc};
+use std::sync::Arc;
 
 use futures::StreamExt;
 use twilight_gateway::{
     stream::{self, ShardEventStream},
-    Config, Event, Intents, ShardId,
+    Config as GatewayConfig, Event, Intents, ShardId,
 };
 
+mod config;
 mod context;
+mod error;
+mod favorites;
+mod history;
+mod inactivity;
 mod interactions;
 mod lavalink;
+mod library;
+mod listening;
 mod queue;
+mod scene_board;
+mod settings;
+mod tables;
+mod tags;
 mod track;
 mod utils;
+mod voice;
 
+use config::Config;
 use context::Context;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Load environment variables from .env file
-    dotenv::dotenv().expect(".env file");
-    // Initialize log tracer
-    tracing_subscriber::fmt::init();
+    // Load environment variables from .env file, if there is one
+    dotenv::dotenv().ok();
     // Setup configurations
-    let token = std::env::var("DISCORD_TOKEN")?;
+    let config = Config::load()?;
+    // Initialize log tracer
+    tracing_subscriber::fmt()
+        .with_max_level(config.log_level())
+        .init();
     let intents = Intents::GUILD_MESSAGES
         | Intents::MESSAGE_CONTENT
         | Intents::GUILDS
         | Intents::GUILD_VOICE_STATES;
 
-    let config = Config::new(token.clone(), intents);
+    let gateway_config = GatewayConfig::new(config.discord_token.clone(), intents);
 
     // Bot context for sharing data across tasks, accessing twilight clients and general setup
-    let ctx = Arc::new(Context::new(token).await?);
+    let ctx = Arc::new(Context::new(config).await?);
 
-    // Setup lavalink variables to connect to the node
-    let lavalink_host = SocketAddr::from_str(&std::env::var("LAVALINK_HOST")?)?;
-    let lavalink_secret = std::env::var("LAVALINK_SECRET")?;
-
-    // Connects and adds a node to the lavalink client
-    // The handle to the node is not used, but the events are used to check for TrackEnd and TrackStart events
+    // Connects and adds the nodes to the lavalink client, players are spread across them
+    // The handles to the nodes are not used,
+    // but the events are used to check for TrackEnd and TrackStart events
     // Used in the tracks queue
-    let (_node, lavalink_events) = ctx.lavalink.add(lavalink_host, lavalink_secret).await?;
+    for node in &ctx.config.lavalink {
+        let (_node, lavalink_events) = ctx
+            .lavalink
+            .add(node.socket_addr()?, node.password.clone())
+            .await?;
+
+        // Separate loop for the lavalink events of each node
+        tokio::spawn(lavalink::handle_events(lavalink_events, ctx.clone()));
+    }
+
+    // Counts the time users spend listening, for /stats
+    tokio::spawn(listening::sample_listeners(ctx.clone()));
+
+    // Indexes the music library and keeps it up to date, when there is one
+    tokio::spawn(library::watch(ctx.clone()));
 
     // Initialize the bot slash commands
     ctx.setup_commands().await?;
 
-    // Initialize shards (currently spawning discord's recommended number of shards, could be only one for small bots)
-    let mut shards =
-        stream::create_recommended(&ctx.http_client, config, |_, builder| builder.build())
-            .await?
-            .collect::<Vec<_>>();
+    // Initialize shards, discord's recommended number of shards unless configured
+    let shards_config = &ctx.config.shards;
+    let mut shards = match shards_config.total {
+        Some(total) => stream::create_range(
+            shards_config.start.unwrap_or(0)..shards_config.end.unwrap_or(total),
+            total,
+            gateway_config,
+            |_, builder| builder.build(),
+        )
+        .collect::<Vec<_>>(),
+        None => stream::create_recommended(&ctx.http_client, gateway_config, |_, builder| {
+            builder.build()
+        })
+        .await?
+        .collect::<Vec<_>>(),
+    };
 
     // Add shard and it's message sender to a hashmap to allow access from across tasks
     for shard in &shards {
@@ -59,9 +96,6 @@ async fn main() -> anyhow::Result<()> {
     // Stream of shard events
     let mut stream = ShardEventStream::new(shards.iter_mut());
 
-    // Separate loop for lavalink events
-    tokio::spawn(lavalink::handle_events(lavalink_events, ctx.clone()));
-
     // Initialize the loop to handle shard events
     while let Some((shard, e)) = stream.next().await {
         let event = match e {
@@ -77,11 +111,33 @@ async fn main() -> anyhow::Result<()> {
             }
         };
 
+        // The bot's previous voice channel is gone from the cache once it's updated
+        let bot_voice_update = match &event {
+            Event::VoiceStateUpdate(update) if update.user_id == ctx.user_id => update
+                .guild_id
+                .map(|guild_id| (guild_id, ctx.bot_voice_channel(guild_id))),
+            _ => None,
+        };
+
         ctx.cache.update(&event);
         ctx.lavalink.process(&event).await?;
+        // After lavalink, so a task waiting for a voice update finds the player ready
+        ctx.standby.process(&event);
+
+        if let Some((guild_id, previous_channel)) = bot_voice_update {
+            if let Err(err) = voice::bot_voice_update(&ctx, guild_id, previous_channel) {
+                tracing::error!("Failed to handle voice update in {}: {:?}", guild_id, err);
+            }
+        }
 
         // Spawn task to handle each shard event
-        tokio::spawn(handle_shard_stream_event(event, ctx.clone(), shard.id()));
+        let task_ctx = ctx.clone();
+        let shard_id = shard.id();
+        tokio::spawn(async move {
+            if let Err(err) = handle_shard_stream_event(event, task_ctx, shard_id).await {
+                tracing::error!("Failed to handle event on shard {}: {:?}", shard_id, err);
+            }
+        });
     }
 
     Ok(())
@@ -105,6 +161,11 @@ async fn handle_shard_stream_event(
         Event::InteractionCreate(interaction) => {
             interactions::handle_interaction(ctx.clone(), interaction.0, shard_id).await?
         }
+        Event::VoiceStateUpdate(update) => {
+            if let Some(guild_id) = update.guild_id {
+                inactivity::check_listeners(&ctx, guild_id)?;
+            }
+        }
         _ => {}
     }
 
@@ -1,13 +1,24 @@
 use std::sync::{Arc, Mutex};
 
 use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use twilight_interactions::command::{CommandOption, CreateOption};
 
 use crate::track::Track;
 
-#[derive(Debug)]
+// Also used as the choices of the /loop command option and stored as a guild setting
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, CommandOption, CreateOption, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
 pub enum QueueLoopMode {
+    #[option(name = "none", value = "none")]
     None,
+    #[option(name = "queue", value = "queue")]
+    #[serde(rename = "queue")]
     LoopQueue,
+    #[option(name = "track", value = "track")]
+    #[serde(rename = "track")]
     LoopTrack,
 }
 
@@ -18,7 +29,6 @@ pub struct TracksQueue {
     pub loop_mode: QueueLoopMode,
 }
 
-
 impl TracksQueue {
     pub fn new() -> Self {
         Self {
@@ -71,6 +81,12 @@ impl TracksQueue {
         inner.clear();
     }
 
+    /// Swap the whole queue, the first track has to be played by the caller
+    pub fn replace(&self, tracks: Vec<Track>) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner = tracks;
+    }
+
     pub fn shuffle(&self) {
         let mut inner = self.inner.lock().unwrap();
 
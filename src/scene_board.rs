@@ -0,0 +1,140 @@
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+use twilight_lavalink::model::EqualizerBand;
+use twilight_model::id::{marker::GuildMarker, Id};
+
+use crate::{error::UserError, queue::QueueLoopMode};
+
+// Discord fits 5 rows of 5 buttons under a message
+const MAX_SCENES: usize = 25;
+
+const MAX_LABEL_LEN: usize = 80;
+
+// Lavalink has 15 bands, from 25 Hz up to 16 kHz
+const EQUALIZER_BANDS: i64 = 15;
+
+/// Scenes of a guild's /sceneboard, read from `<data_dir>/scenes/<guild id>.toml`.
+/// See `scenes.example.toml`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneBoard {
+    #[serde(rename = "scene")]
+    pub scenes: Vec<BoardScene>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoardScene {
+    pub label: String,
+    /// A unicode emoji shown before the label
+    pub emoji: Option<String>,
+    /// Tag whose tracks are shuffled, see /tag
+    pub tag: Option<String>,
+    /// Link loaded like /play, used instead of a tag
+    pub playlist: Option<String>,
+    /// Shuffle the playlist, tags are always shuffled
+    #[serde(default)]
+    pub shuffle: bool,
+    #[serde(rename = "loop")]
+    pub loop_mode: Option<QueueLoopMode>,
+    /// The guild's default volume when unset
+    pub volume: Option<u16>,
+    #[serde(default)]
+    pub filter: EqualizerPreset,
+}
+
+/// Equalizer presets of the scenes, so a GM doesn't have to tune 15 bands
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EqualizerPreset {
+    /// The tracks as they are
+    #[default]
+    Flat,
+    /// Heavier lows, for fights and clubs
+    Bass,
+    /// Lows only, like music heard through a wall
+    Muffled,
+    /// Mids only, like a cheap speaker or a comm channel
+    Radio,
+}
+
+impl EqualizerPreset {
+    pub fn bands(self) -> Vec<EqualizerBand> {
+        (0..EQUALIZER_BANDS)
+            .map(|band| EqualizerBand::new(band, self.gain(band)))
+            .collect()
+    }
+
+    fn gain(self, band: i64) -> f64 {
+        match self {
+            Self::Flat => 0.0,
+            Self::Bass => match band {
+                0..=2 => 0.3,
+                3..=4 => 0.15,
+                _ => 0.0,
+            },
+            Self::Muffled => match band {
+                0..=3 => 0.1,
+                4..=5 => -0.1,
+                _ => -0.25,
+            },
+            Self::Radio => match band {
+                0..=3 => -0.25,
+                6..=9 => 0.2,
+                11..=14 => -0.25,
+                _ => 0.0,
+            },
+        }
+    }
+}
+
+impl SceneBoard {
+    pub fn load(data_dir: &Path, guild_id: Id<GuildMarker>) -> anyhow::Result<Self> {
+        let path = data_dir.join("scenes").join(format!("{}.toml", guild_id));
+        if !path.exists() {
+            return Err(UserError::NoSceneBoard.into());
+        }
+
+        let board: Self = toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|err| UserError::InvalidSceneBoard(err.message().to_owned()))?;
+        board.validate()?;
+
+        Ok(board)
+    }
+
+    fn validate(&self) -> Result<(), UserError> {
+        if self.scenes.is_empty() || self.scenes.len() > MAX_SCENES {
+            return Err(UserError::InvalidSceneBoard(format!(
+                "a board has between 1 and {} scenes",
+                MAX_SCENES
+            )));
+        }
+
+        for (i, scene) in self.scenes.iter().enumerate() {
+            let invalid = |reason: &str| {
+                UserError::InvalidSceneBoard(format!(
+                    "scene {} ({}) {}",
+                    i + 1,
+                    scene.label,
+                    reason
+                ))
+            };
+
+            if scene.label.is_empty() || scene.label.chars().count() > MAX_LABEL_LEN {
+                return Err(invalid(&format!(
+                    "needs a label of at most {} characters",
+                    MAX_LABEL_LEN
+                )));
+            }
+            if scene.tag.is_some() == scene.playlist.is_some() {
+                return Err(invalid("needs either a tag or a playlist"));
+            }
+            if scene.volume.is_some_and(|volume| volume > 1000) {
+                return Err(invalid("has a volume over 1000"));
+            }
+        }
+
+        Ok(())
+    }
+}
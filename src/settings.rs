@@ -0,0 +1,159 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use twilight_lavalink::http::Track as TwilightTrack;
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker, RoleMarker},
+    Id,
+};
+
+use crate::{error::UserError, queue::QueueLoopMode, utils::save_json};
+
+// Commands only DJs can use until a guild changes it, scenes belong to the GM.
+// Everyone else skips through /voteskip
+const DEFAULT_DJ_COMMANDS: [&str; 8] = [
+    "stop",
+    "leave",
+    "loop",
+    "skip",
+    "scene",
+    "sceneboard",
+    "tag",
+    "table",
+];
+
+/// Per guild configuration, changed through the admin commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Role that counts as DJ, when unset a role named "dj" is used
+    pub dj_role: Option<Id<RoleMarker>>,
+    /// Names of the commands that only a DJ can use
+    pub dj_commands: Vec<String>,
+    /// A user alone in the voice channel with the bot counts as DJ
+    pub alone_is_dj: bool,
+    /// Percentage of the listeners that has to vote to skip a track
+    pub vote_skip_percent: u8,
+    /// Channel for the now playing messages, when unset they go where the track was queued
+    pub announce_channel: Option<Id<ChannelMarker>>,
+    pub announce_now_playing: bool,
+    /// Player volume set when joining a voice channel, from 0 to 1000
+    pub default_volume: u16,
+    /// Loop mode of the queue when it's created or the bot leaves
+    pub default_loop: QueueLoopMode,
+    pub max_queue_length: Option<usize>,
+    /// Longest track that can be queued, in seconds
+    pub max_track_duration: Option<u64>,
+    pub embed_color: u32,
+    pub queue_page_size: usize,
+    /// Stay in the voice channel instead of leaving when it's empty or idle (/247)
+    pub stay_connected: bool,
+    /// Pause when the voice channel has no listeners and resume when someone joins,
+    /// instead of leaving
+    pub auto_pause: bool,
+    /// Seconds to wait before leaving a voice channel without listeners
+    pub leave_empty_after: u64,
+    /// Seconds to wait before leaving when there is nothing to play
+    pub leave_idle_after: u64,
+    /// Keep the queue when someone disconnects the bot, it starts again on /join
+    pub keep_queue_on_disconnect: bool,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            dj_role: None,
+            dj_commands: DEFAULT_DJ_COMMANDS.map(String::from).to_vec(),
+            alone_is_dj: true,
+            vote_skip_percent: 50,
+            announce_channel: None,
+            announce_now_playing: true,
+            default_volume: 100,
+            default_loop: QueueLoopMode::None,
+            max_queue_length: None,
+            max_track_duration: None,
+            embed_color: 0xe04f2e,
+            queue_page_size: 10,
+            stay_connected: false,
+            auto_pause: false,
+            leave_empty_after: 60,
+            leave_idle_after: 10 * 60,
+            keep_queue_on_disconnect: false,
+        }
+    }
+}
+
+impl GuildSettings {
+    pub fn is_dj_command(&self, name: &str) -> bool {
+        self.dj_commands.iter().any(|command| command == name)
+    }
+
+    /// Check the track against the guild's limits before adding it to a queue of `queue_len` tracks
+    pub fn check_track(&self, queue_len: usize, track: &TwilightTrack) -> Result<(), UserError> {
+        if let Some(max) = self.max_queue_length {
+            if queue_len >= max {
+                return Err(UserError::QueueFull { max });
+            }
+        }
+
+        if let Some(max_secs) = self.max_track_duration {
+            if track.info.length > max_secs.saturating_mul(1000) {
+                return Err(UserError::TrackTooLong { max_secs });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Settings of every guild, kept in memory and written to a json file on every change
+pub struct SettingsStore {
+    path: PathBuf,
+    guilds: DashMap<Id<GuildMarker>, GuildSettings>,
+}
+
+impl SettingsStore {
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+
+        let guilds = if path.exists() {
+            serde_json::from_str::<HashMap<Id<GuildMarker>, GuildSettings>>(&fs::read_to_string(
+                &path,
+            )?)?
+            .into_iter()
+            .collect()
+        } else {
+            DashMap::default()
+        };
+
+        Ok(Self { path, guilds })
+    }
+
+    /// Settings of the guild, the defaults if it never changed any
+    pub fn get(&self, guild_id: Id<GuildMarker>) -> GuildSettings {
+        self.guilds
+            .get(&guild_id)
+            .map(|settings| settings.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn update(
+        &self,
+        guild_id: Id<GuildMarker>,
+        f: impl FnOnce(&mut GuildSettings),
+    ) -> anyhow::Result<()> {
+        f(&mut self.guilds.entry(guild_id).or_default());
+        self.save()
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let guilds = self
+            .guilds
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect::<HashMap<_, _>>();
+
+        save_json(&self.path, &guilds)
+    }
+}
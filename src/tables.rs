@@ -0,0 +1,388 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use twilight_model::id::{marker::GuildMarker, Id};
+
+use crate::utils::save_json;
+
+// Dice from a coin flip to a d100
+const MIN_SIDES: u8 = 2;
+const MAX_SIDES: u8 = 100;
+
+/// Table names are matched case insensitively, like tags
+pub fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Random table of a guild, each result covers a range of the die.
+/// Same shape in the store and in imported TOML or JSON files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Table {
+    /// Sides of the die rolled
+    pub die: u8,
+    #[serde(default, rename = "result")]
+    pub results: Vec<TableResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableResult {
+    pub roll: Roll,
+    /// Read out when the result comes up
+    pub text: String,
+    /// A random track of the tag is played
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Link or search loaded like /play, used instead of a tag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<String>,
+}
+
+/// Die results covered by a table result, written `3` or `1-2`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RollValue", into = "String")]
+pub struct Roll {
+    pub min: u8,
+    pub max: u8,
+}
+
+// Files can write a single number without quotes
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RollValue {
+    Number(u8),
+    Range(String),
+}
+
+impl Roll {
+    fn contains(&self, roll: u8) -> bool {
+        (self.min..=self.max).contains(&roll)
+    }
+
+    fn overlaps(&self, other: &Roll) -> bool {
+        self.min <= other.max && other.min <= self.max
+    }
+}
+
+impl FromStr for Roll {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("`{}` is not a roll, write it like `3` or `1-2`", s);
+
+        let (min, max) = match s.split_once('-') {
+            Some((min, max)) => (min.trim(), max.trim()),
+            None => (s.trim(), s.trim()),
+        };
+        let min = min.parse().map_err(|_| invalid())?;
+        let max = max.parse().map_err(|_| invalid())?;
+        if min > max {
+            return Err(invalid());
+        }
+
+        Ok(Self { min, max })
+    }
+}
+
+impl TryFrom<RollValue> for Roll {
+    type Error = String;
+
+    fn try_from(value: RollValue) -> Result<Self, Self::Error> {
+        match value {
+            RollValue::Number(roll) => Ok(Self {
+                min: roll,
+                max: roll,
+            }),
+            RollValue::Range(range) => range.parse(),
+        }
+    }
+}
+
+impl From<Roll> for String {
+    fn from(roll: Roll) -> Self {
+        roll.to_string()
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{}-{}", self.min, self.max)
+        }
+    }
+}
+
+impl Table {
+    pub fn new(die: u8) -> Self {
+        Self {
+            die,
+            results: Vec::new(),
+        }
+    }
+
+    /// Result covering the roll, rolls without one are left to the GM
+    pub fn lookup(&self, roll: u8) -> Option<&TableResult> {
+        self.results
+            .iter()
+            .find(|result| result.roll.contains(roll))
+    }
+
+    /// Set the result, replacing the ones it overlaps
+    pub fn set(&mut self, result: TableResult) {
+        self.results
+            .retain(|existing| !existing.roll.overlaps(&result.roll));
+        self.results.push(result);
+        self.results.sort_by_key(|result| result.roll.min);
+    }
+
+    /// Remove the result covering the roll
+    pub fn remove(&mut self, roll: u8) -> Option<TableResult> {
+        let index = self
+            .results
+            .iter()
+            .position(|result| result.roll.contains(roll))?;
+
+        Some(self.results.remove(index))
+    }
+
+    /// Problems of the table, written for the user
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_SIDES..=MAX_SIDES).contains(&self.die) {
+            return Err(format!(
+                "the die has between {} and {} sides",
+                MIN_SIDES, MAX_SIDES
+            ));
+        }
+
+        for (i, result) in self.results.iter().enumerate() {
+            if result.roll.min < 1 || result.roll.max > self.die {
+                return Err(format!("roll {} is not on a d{}", result.roll, self.die));
+            }
+            if result.tag.is_some() && result.track.is_some() {
+                return Err(format!("roll {} has both a tag and a track", result.roll));
+            }
+            if let Some(other) = self.results[..i]
+                .iter()
+                .find(|other| other.roll.overlaps(&result.roll))
+            {
+                return Err(format!("rolls {} and {} overlap", other.roll, result.roll));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Random tables of each guild, saved as JSON after every change
+pub struct TableStore {
+    path: PathBuf,
+    guilds: DashMap<Id<GuildMarker>, BTreeMap<String, Table>>,
+}
+
+impl TableStore {
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+
+        let guilds = if path.exists() {
+            serde_json::from_str::<HashMap<Id<GuildMarker>, BTreeMap<String, Table>>>(
+                &fs::read_to_string(&path)?,
+            )?
+            .into_iter()
+            .collect()
+        } else {
+            DashMap::default()
+        };
+
+        Ok(Self { path, guilds })
+    }
+
+    /// Tables of the guild, by name
+    pub fn all(&self, guild_id: Id<GuildMarker>) -> BTreeMap<String, Table> {
+        self.guilds
+            .get(&guild_id)
+            .map(|tables| tables.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, guild_id: Id<GuildMarker>, name: &str) -> Option<Table> {
+        self.guilds
+            .get(&guild_id)
+            .and_then(|tables| tables.get(name).cloned())
+    }
+
+    /// Create an empty table, `false` if there is one with that name already
+    pub fn create(&self, guild_id: Id<GuildMarker>, name: &str, die: u8) -> anyhow::Result<bool> {
+        let created = {
+            let mut tables = self.guilds.entry(guild_id).or_default();
+            if tables.contains_key(name) {
+                false
+            } else {
+                tables.insert(name.to_owned(), Table::new(die));
+                true
+            }
+        };
+
+        if created {
+            self.save()?;
+        }
+
+        Ok(created)
+    }
+
+    /// Change the table, `None` if it doesn't exist
+    pub fn update<T>(
+        &self,
+        guild_id: Id<GuildMarker>,
+        name: &str,
+        f: impl FnOnce(&mut Table) -> T,
+    ) -> anyhow::Result<Option<T>> {
+        let updated = self
+            .guilds
+            .get_mut(&guild_id)
+            .and_then(|mut tables| tables.get_mut(name).map(f));
+
+        if updated.is_some() {
+            self.save()?;
+        }
+
+        Ok(updated)
+    }
+
+    /// `false` if there was no such table
+    pub fn delete(&self, guild_id: Id<GuildMarker>, name: &str) -> anyhow::Result<bool> {
+        let deleted = self
+            .guilds
+            .get_mut(&guild_id)
+            .is_some_and(|mut tables| tables.remove(name).is_some());
+
+        if deleted {
+            self.save()?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Add the tables, replacing the ones with the same names
+    pub fn import(
+        &self,
+        guild_id: Id<GuildMarker>,
+        imported: BTreeMap<String, Table>,
+    ) -> anyhow::Result<()> {
+        self.guilds.entry(guild_id).or_default().extend(imported);
+
+        self.save()
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let guilds = self
+            .guilds
+            .iter()
+            .filter(|entry| !entry.value().is_empty())
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect::<HashMap<_, _>>();
+
+        save_json(&self.path, &guilds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(roll: &str, text: &str) -> TableResult {
+        TableResult {
+            roll: roll.parse().unwrap(),
+            text: text.to_owned(),
+            tag: None,
+            track: None,
+        }
+    }
+
+    #[test]
+    fn parses_rolls() {
+        assert_eq!("3".parse(), Ok(Roll { min: 3, max: 3 }));
+        assert_eq!("1-2".parse(), Ok(Roll { min: 1, max: 2 }));
+        assert_eq!(" 4 - 6 ".parse(), Ok(Roll { min: 4, max: 6 }));
+
+        assert!("".parse::<Roll>().is_err());
+        assert!("6-4".parse::<Roll>().is_err());
+        assert!("1-".parse::<Roll>().is_err());
+        assert!("d6".parse::<Roll>().is_err());
+        assert!("300".parse::<Roll>().is_err());
+    }
+
+    #[test]
+    fn set_replaces_overlapping_results() {
+        let mut table = Table::new(6);
+        table.set(result("1-3", "low"));
+        table.set(result("4-6", "high"));
+        table.set(result("3-4", "middle"));
+
+        let texts = table
+            .results
+            .iter()
+            .map(|result| result.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["middle"]);
+
+        table.set(result("6", "six"));
+        table.set(result("1", "one"));
+        assert_eq!(table.lookup(1).unwrap().text, "one");
+        assert_eq!(table.lookup(4).unwrap().text, "middle");
+        assert!(table.lookup(5).is_none());
+    }
+
+    #[test]
+    fn validates_tables() {
+        let mut table = Table::new(6);
+        table.results = vec![result("1-3", "low"), result("4-6", "high")];
+        assert!(table.validate().is_ok());
+
+        table.results.push(result("7", "off the die"));
+        assert!(table.validate().is_err());
+
+        table.results = vec![result("1-3", "low"), result("3-6", "high")];
+        assert!(table.validate().is_err());
+
+        let mut both = result("1", "both");
+        both.tag = Some("combat".to_owned());
+        both.track = Some("ytsearch:battle".to_owned());
+        table.results = vec![both];
+        assert!(table.validate().is_err());
+
+        assert!(Table::new(1).validate().is_err());
+        assert!(Table::new(101).validate().is_err());
+    }
+
+    #[test]
+    fn reads_numbers_and_ranges_from_files() {
+        let table: Table = toml::from_str(
+            r#"
+            die = 4
+
+            [[result]]
+            roll = 1
+            text = "one"
+
+            [[result]]
+            roll = "2-4"
+            text = "the rest"
+            tag = "combat"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(table.results[0].roll, Roll { min: 1, max: 1 });
+        assert_eq!(table.results[1].roll, Roll { min: 2, max: 4 });
+        assert!(table.validate().is_ok());
+    }
+}
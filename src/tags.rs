@@ -0,0 +1,123 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+};
+
+use dashmap::DashMap;
+use twilight_lavalink::http::Track as TwilightTrack;
+use twilight_model::id::{marker::GuildMarker, Id};
+
+use crate::utils::save_json;
+
+/// Tags are matched case insensitively, `Combat ` and `combat` are the same tag
+pub fn normalize(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Tracks of each guild grouped by mood tags, the pools scenes pick from.
+/// Saved as JSON after every change, like the favorites
+pub struct TagStore {
+    path: PathBuf,
+    guilds: DashMap<Id<GuildMarker>, BTreeMap<String, Vec<TwilightTrack>>>,
+}
+
+impl TagStore {
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+
+        let guilds = if path.exists() {
+            serde_json::from_str::<HashMap<Id<GuildMarker>, BTreeMap<String, Vec<TwilightTrack>>>>(
+                &fs::read_to_string(&path)?,
+            )?
+            .into_iter()
+            .collect()
+        } else {
+            DashMap::default()
+        };
+
+        Ok(Self { path, guilds })
+    }
+
+    /// Tags of the guild and how many tracks each has, in alphabetical order
+    pub fn tags(&self, guild_id: Id<GuildMarker>) -> Vec<(String, usize)> {
+        self.guilds
+            .get(&guild_id)
+            .map(|tags| {
+                tags.iter()
+                    .map(|(tag, tracks)| (tag.clone(), tracks.len()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Tracks with the tag, in the order they were tagged
+    pub fn pool(&self, guild_id: Id<GuildMarker>, tag: &str) -> Vec<TwilightTrack> {
+        self.guilds
+            .get(&guild_id)
+            .and_then(|tags| tags.get(tag).cloned())
+            .unwrap_or_default()
+    }
+
+    /// Tag the track, `false` if it already had the tag
+    pub fn add(
+        &self,
+        guild_id: Id<GuildMarker>,
+        tag: &str,
+        track: TwilightTrack,
+    ) -> anyhow::Result<bool> {
+        let added = {
+            let mut tags = self.guilds.entry(guild_id).or_default();
+            let tracks = tags.entry(tag.to_owned()).or_default();
+            if tracks
+                .iter()
+                .any(|tagged| tagged.info.identifier == track.info.identifier)
+            {
+                false
+            } else {
+                tracks.push(track);
+                true
+            }
+        };
+
+        if added {
+            self.save()?;
+        }
+
+        Ok(added)
+    }
+
+    /// Untag the track at the index of the pool, the tag is gone with its last track
+    pub fn remove(
+        &self,
+        guild_id: Id<GuildMarker>,
+        tag: &str,
+        index: usize,
+    ) -> anyhow::Result<Option<TwilightTrack>> {
+        let removed = self.guilds.get_mut(&guild_id).and_then(|mut tags| {
+            let tracks = tags.get_mut(tag).filter(|tracks| index < tracks.len())?;
+            let removed = tracks.remove(index);
+            if tracks.is_empty() {
+                tags.remove(tag);
+            }
+            Some(removed)
+        });
+
+        if removed.is_some() {
+            self.save()?;
+        }
+
+        Ok(removed)
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let guilds = self
+            .guilds
+            .iter()
+            .filter(|entry| !entry.value().is_empty())
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect::<HashMap<_, _>>();
+
+        save_json(&self.path, &guilds)
+    }
+}
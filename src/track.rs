@@ -1,18 +1,31 @@
 use twilight_lavalink::http::{Track as TwilightTrack, TrackInfo};
-use twilight_model::id::{marker::ChannelMarker, Id};
+use twilight_model::id::{
+    marker::{ChannelMarker, UserMarker},
+    Id,
+};
 
 // Wrapper over twilight_lavalink track to add extra context to help embed displaying
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Track {
     inner: TwilightTrack,
     pub channel_id: Id<ChannelMarker>,
+    /// User who queued the track
+    pub requester: Id<UserMarker>,
+    /// Where the track starts in ms, set when a scene cut it so it resumes there
+    pub start_time: Option<u64>,
 }
 
 impl Track {
-    pub fn new(track: TwilightTrack, channel_id: Id<ChannelMarker>) -> Self {
+    pub fn new(
+        track: TwilightTrack,
+        channel_id: Id<ChannelMarker>,
+        requester: Id<UserMarker>,
+    ) -> Self {
         Self {
             inner: track,
             channel_id,
+            requester,
+            start_time: None,
         }
     }
 
@@ -20,6 +33,10 @@ impl Track {
         &self.inner.info
     }
 
+    pub fn inner(&self) -> &TwilightTrack {
+        &self.inner
+    }
+
     pub fn track(&self) -> String {
         self.inner.track.clone()
     }
@@ -1,11 +1,15 @@
-use std::sync::Arc;
-
-use twilight_model::id::{
-    marker::{GuildMarker, UserMarker},
-    Id,
+use std::{
+    fs,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::context::Context;
+use serde::Serialize;
+use twilight_lavalink::http::Track as TwilightTrack;
+
+// Saves of every store go one at a time, they would overwrite each other's temporary file
+static SAVE_LOCK: Mutex<()> = Mutex::new(());
 
 pub fn from_ms_to_minutes(ms: u64) -> String {
     let minutes = (ms as f64 / 60000.0).floor() as i32;
@@ -19,10 +23,61 @@ pub fn from_ms_to_minutes(ms: u64) -> String {
         format!("{}:{}", minutes, seconds)
     }
 }
-pub fn check_voice_state(
-    ctx: Arc<Context>,
-    author_id: Id<UserMarker>,
-    guild_id: Id<GuildMarker>,
-) -> bool {
-    matches!(ctx.cache.voice_state(author_id, guild_id), Some(_vc))
+
+/// Join lines until `max_len` is reached (embed field values are limited to 1024 characters)
+pub fn truncate_lines(lines: &[String], max_len: usize) -> String {
+    let mut value = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if value.len() + line.len() + 1 > max_len - 16 {
+            value.push_str(&format!("...and {} more", lines.len() - i));
+            break;
+        }
+        value.push_str(line);
+        value.push('\n');
+    }
+
+    value
+}
+
+/// Line listing a track in the summary of the commands that queue many at once
+pub fn track_line(track: &TwilightTrack) -> String {
+    format!(
+        "**[{}]({})**",
+        track.info.title.clone().unwrap_or("<Unknown>".to_string()),
+        track.info.uri
+    )
+}
+
+/// Longer durations than track lengths, like `3h 05m`
+pub fn format_duration(secs: i64) -> String {
+    let minutes = secs / 60;
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// Seconds since the unix epoch, used for the timestamps in the history
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Write the value as pretty JSON, through a temporary file so a crash can't leave
+/// a half written file
+pub fn save_json(path: &Path, value: &impl Serialize) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+
+    let _lock = SAVE_LOCK.lock().unwrap();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json)?;
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
 }
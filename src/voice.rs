@@ -0,0 +1,240 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use twilight_gateway::Event;
+use twilight_http::{request::Request, response::marker::EmptyBody, routing::Route};
+use twilight_lavalink::model::{Play, Volume};
+use twilight_model::{
+    gateway::payload::outgoing::UpdateVoiceState,
+    id::{
+        marker::{ChannelMarker, GuildMarker, UserMarker},
+        Id,
+    },
+    util::Timestamp,
+};
+
+use crate::{context::Context, error::UserError};
+
+// How long discord has to hand over the voice connection after joining
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Longest stage topic discord accepts
+const MAX_STAGE_TOPIC_LEN: usize = 120;
+
+/// How the bot ended up in the channel
+pub enum Joined {
+    Voice,
+    StageSpeaker,
+    /// Couldn't become a speaker on its own, the request to speak is up to the stage moderators
+    StageRequestedToSpeak,
+}
+
+/// Voice channel of the user that a command queueing tracks has to join first, `None` if the bot
+/// is already there. Being connected to another channel of the guild is refused
+pub fn channel_to_join(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+) -> Result<Option<Id<ChannelMarker>>, UserError> {
+    let user_channel = ctx
+        .cache
+        .voice_state(user_id, guild_id)
+        .map(|voice_state| voice_state.channel_id())
+        .ok_or(UserError::UserNotInVoice)?;
+
+    match ctx.bot_voice_channel(guild_id) {
+        None => Ok(Some(user_channel)),
+        Some(bot_channel) if bot_channel == user_channel => Ok(None),
+        Some(_) => Err(UserError::UserNotInBotChannel),
+    }
+}
+
+/// Join (or move to) the voice channel and wait until the lavalink player can play in it
+pub async fn connect(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    channel_id: Id<ChannelMarker>,
+) -> anyhow::Result<Joined> {
+    let current_channel = ctx.bot_voice_channel(guild_id);
+
+    if current_channel != Some(channel_id) {
+        let sender = ctx.shard_sender(guild_id).ok_or(anyhow::anyhow!(
+            "No message sender for the shard of guild id {}",
+            guild_id
+        ))?;
+
+        // Moving keeps the voice connection, so it's only the bot's voice state to wait for.
+        // Otherwise lavalink gets the connection from the voice server update.
+        // The wait is registered before joining so the event can't be missed
+        let moving = current_channel.is_some();
+        let user_id = ctx.user_id;
+        let ready = ctx
+            .standby
+            .wait_for(guild_id, move |event: &Event| match event {
+                Event::VoiceServerUpdate(_) => !moving,
+                Event::VoiceStateUpdate(update) => {
+                    moving && update.user_id == user_id && update.channel_id == Some(channel_id)
+                }
+                _ => false,
+            });
+
+        sender.command(&UpdateVoiceState::new(guild_id, channel_id, false, false))?;
+
+        tokio::time::timeout(CONNECT_TIMEOUT, ready)
+            .await
+            .map_err(|_| UserError::VoiceConnectTimeout)??;
+
+        if !moving {
+            let volume = ctx.settings.get(guild_id).default_volume;
+            let player = ctx.lavalink.player(guild_id).await?;
+            player.send(Volume::from((guild_id, volume as i64)))?;
+        }
+    }
+
+    if !ctx.is_stage_channel(channel_id) {
+        return Ok(Joined::Voice);
+    }
+
+    become_speaker(ctx, guild_id, channel_id).await
+}
+
+/// Bots join stages as audience, unsuppressing needs the mute members permission.
+/// Without it the bot raises its hand instead
+async fn become_speaker(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    channel_id: Id<ChannelMarker>,
+) -> anyhow::Result<Joined> {
+    let route = Route::UpdateCurrentUserVoiceState {
+        guild_id: guild_id.get(),
+    };
+
+    // twilight's request builder can only set suppress to true, so this one is built by hand
+    let unsuppress = Request::builder(&route)
+        .json(&serde_json::json!({ "channel_id": channel_id, "suppress": false }))?
+        .build();
+    if ctx
+        .http_client
+        .request::<EmptyBody>(unsuppress)
+        .await
+        .is_ok()
+    {
+        return Ok(Joined::StageSpeaker);
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let timestamp = Timestamp::from_secs(now as i64)?.iso_8601().to_string();
+    ctx.http_client
+        .update_current_user_voice_state(guild_id)
+        .channel_id(channel_id)
+        .request_to_speak_timestamp(&timestamp)
+        .await?;
+
+    Ok(Joined::StageRequestedToSpeak)
+}
+
+/// Set the topic of the stage the bot is in to the current track, starting the stage if needed
+pub async fn update_stage_topic(ctx: &Context, guild_id: Id<GuildMarker>) -> anyhow::Result<()> {
+    let Some(channel_id) = ctx
+        .bot_voice_channel(guild_id)
+        .filter(|channel_id| ctx.is_stage_channel(*channel_id))
+    else {
+        return Ok(());
+    };
+
+    let Some(track) = ctx
+        .get_queue(guild_id)
+        .and_then(|queue| queue.lock().unwrap().peek().ok())
+    else {
+        return Ok(());
+    };
+
+    let topic = format!(
+        "{} - {}",
+        track
+            .info()
+            .title
+            .clone()
+            .unwrap_or("<Unknown>".to_string()),
+        track
+            .info()
+            .author
+            .clone()
+            .unwrap_or("<Unknown>".to_string())
+    )
+    .chars()
+    .take(MAX_STAGE_TOPIC_LEN)
+    .collect::<String>();
+
+    // Only fails when the stage isn't live, then starting it with the topic is the way to set it
+    let updated = ctx
+        .http_client
+        .update_stage_instance(channel_id)
+        .topic(&topic)?
+        .await;
+    if updated.is_err() {
+        ctx.http_client
+            .create_stage_instance(channel_id, &topic)?
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The bot's own voice state changed, `previous` is the channel it was in before.
+/// Handles being disconnected or moved by someone else
+pub fn bot_voice_update(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    previous: Option<Id<ChannelMarker>>,
+) -> anyhow::Result<()> {
+    match (previous, ctx.bot_voice_channel(guild_id)) {
+        (Some(channel_id), None) => disconnected(ctx, guild_id, channel_id),
+        (Some(from), Some(to)) if from != to => {
+            // The voice connection follows the bot, the player keeps playing on its own.
+            // Votes were counted among the listeners of the previous channel
+            tracing::debug!("Moved from {} to {} in guild {}", from, to, guild_id);
+            ctx.skip_votes.remove(&guild_id);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn disconnected(
+    ctx: &Context,
+    guild_id: Id<GuildMarker>,
+    channel_id: Id<ChannelMarker>,
+) -> anyhow::Result<()> {
+    tracing::debug!("Disconnected from {} in guild {}", channel_id, guild_id);
+
+    // Also the case after /leave, which already did all of this
+    ctx.lavalink.players().destroy(guild_id)?;
+    ctx.skip_votes.remove(&guild_id);
+    ctx.inactivity.clear(guild_id);
+
+    let settings = ctx.settings.get(guild_id);
+    if !settings.keep_queue_on_disconnect {
+        if let Some(queue_arc) = ctx.get_queue(guild_id) {
+            let mut queue = queue_arc.lock().unwrap();
+            queue.clear();
+            queue.loop_mode = settings.default_loop;
+        }
+    }
+
+    Ok(())
+}
+
+/// Start the queue kept from a previous connection, if there is one
+pub async fn resume_kept_queue(ctx: &Context, guild_id: Id<GuildMarker>) -> anyhow::Result<bool> {
+    let Some(track) = ctx
+        .get_queue(guild_id)
+        .and_then(|queue| queue.lock().unwrap().peek().ok())
+    else {
+        return Ok(false);
+    };
+
+    let player = ctx.lavalink.player(guild_id).await?;
+    player.send(Play::from((guild_id, track.track(), track.start_time)))?;
+
+    Ok(true)
+}